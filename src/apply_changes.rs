@@ -1,82 +1,267 @@
-#![expect(clippy::non_ascii_literal)]
+use chrono::{DateTime, NaiveTime, TimeDelta};
+use chrono_tz::Tz;
 
 use crate::generate_ics::{EventStatus, SoonToBeIcsEvent};
-use crate::userconfig::{Change, RemovedEvents};
+use crate::userconfig::{
+    Addition, Change, ChangeKind, Language, Modification, RemovedEventMarks, RemovedEvents,
+};
+use crate::{local_time, messages};
 
-pub fn apply_changes(
-    events: &mut Vec<SoonToBeIcsEvent>,
-    changes: Vec<Change>,
-    removed_events: RemovedEvents,
-) -> anyhow::Result<()> {
-    for change in changes {
-        apply_change(events, change, removed_events)?;
-    }
-    Ok(())
+/// Source of events which are added via a [`Change`] and are not part of an eventfile
+const ADDED_EVENT_SOURCE: &str = "userconfig";
+
+/// Changes which did not exactly match an event.
+/// The Telegram bot has to solve them via user feedback.
+#[derive(Debug, Default)]
+pub struct Mismatches {
+    /// Changes whose event does not exist (anymore) for example because the downloader shifted it
+    pub unmatched: Vec<Change>,
+    /// Changes applied to the closest event with the same name within the tolerance
+    pub fuzzy: Vec<Change>,
 }
 
-#[expect(clippy::suspicious_operation_groupings)]
-fn apply_change(
+/// Apply the changes to the events.
+///
+/// Every change comes with the current name of its event which is used for matching.
+/// The change itself is reported unaltered so the user recognizes it.
+///
+/// Changes are applied to the event with the same name and start.
+/// When there is none the closest start on the same day within `tolerance` is used instead.
+/// A `tolerance` of zero only allows exact matches.
+/// Multiple changes can apply to the same event when they match exactly.
+/// An event already changed by another change is not used as fallback.
+///
+/// Times of the changes are local times of the event or of the `timezone` for added events.
+/// When such a local time is ambiguous or does not exist a warning is added.
+///
+/// Generated texts like the description of added events are in the `language`.
+pub fn apply_changes(
     events: &mut Vec<SoonToBeIcsEvent>,
-    change: Change,
-    removed_events: RemovedEvents,
-) -> anyhow::Result<()> {
-    if change.add {
-        let end_time = change
-            .endtime
-            .ok_or_else(|| anyhow::anyhow!("change add has no end_time specified"))?;
-        let end_time = change.date.date().and_time(end_time);
-
-        events.push(SoonToBeIcsEvent {
-            pretty_name: if let Some(namesuffix) = change.namesuffix {
-                format!("{} {namesuffix}", change.name)
-            } else {
-                change.name.clone()
-            },
-            name: change.name,
-            status: EventStatus::Confirmed,
-            start_time: change.date,
-            end_time,
-            alert_minutes_before: None,
-            description: "Dies ist eine zusätzliche Veranstaltung welche manuell von dir über den Telegram Bot hinzufügt wurde.".to_owned(),
-            location: change.room.unwrap_or_default(),
-        });
-    } else if let Some(i) = events
-        .iter()
-        .position(|event| event.name == change.name && event.start_time == change.date)
-    {
+    changes: Vec<(String, Change)>,
+    language: Language,
+    removed_events: &RemovedEvents,
+    tolerance: TimeDelta,
+    timezone: Tz,
+    warnings: &mut Vec<String>,
+) -> Mismatches {
+    let targets = find_events(events, &changes, tolerance, timezone);
+
+    let mut mismatches = Mismatches::default();
+    // Removed at the end so the indices of the targets stay valid
+    let mut removed = Vec::new();
+    for ((name, change), target) in changes.into_iter().zip(targets) {
+        let (remove, modification) = match &change.kind {
+            ChangeKind::Add(addition) => {
+                let date = change.date.with_timezone(&timezone);
+                events.push(create_added_event(
+                    &name, date, addition, language, warnings,
+                ));
+                continue;
+            }
+            ChangeKind::Remove(modification) => (true, modification),
+            ChangeKind::Modify(modification) => (false, modification),
+            ChangeKind::Invalid(_) => {
+                // Listed so the user is asked to fix or delete it
+                warnings.extend(change.problem());
+                mismatches.unmatched.push(change);
+                continue;
+            }
+        };
+
+        let Some((i, fuzzy)) = target else {
+            // Event for this change doesnt exist.
+            mismatches.unmatched.push(change);
+            continue;
+        };
         let event = &mut events[i];
-        if change.remove {
+        if remove {
             match removed_events {
-                RemovedEvents::Cancelled => event.status = EventStatus::Cancelled,
-                RemovedEvents::Emoji => event.pretty_name = format!("🚫 {}", event.pretty_name),
-                RemovedEvents::Removed => {
-                    events.remove(i);
-                    return Ok(());
-                }
+                RemovedEvents::Removed => removed.push(i),
+                RemovedEvents::Marked(marks) => mark_removed(event, marks, language),
             }
         }
-
-        if let Some(namesuffix) = change.namesuffix {
-            event.pretty_name = format!("{} {namesuffix}", event.pretty_name);
+        apply_modification(event, modification, warnings);
+        if fuzzy {
+            mismatches.fuzzy.push(change);
         }
+    }
 
-        if let Some(room) = change.room {
-            event.location = room;
-        }
+    removed.sort_unstable();
+    // Multiple changes can remove the same event
+    removed.dedup();
+    for i in removed.into_iter().rev() {
+        events.remove(i);
+    }
+    mismatches
+}
+
+fn mark_removed(event: &mut SoonToBeIcsEvent, marks: &RemovedEventMarks, language: Language) {
+    if marks.cancelled {
+        event.status = EventStatus::Cancelled;
+    }
+    if let Some(prefix) = &marks.prefix {
+        event.pretty_name = format!("{prefix} {}", event.pretty_name);
+    }
+    if marks.emoji {
+        event.pretty_name = format!(
+            "{} {}",
+            messages::removed_marker(language),
+            event.pretty_name
+        );
+    }
+    if marks.transparent {
+        event.transparent = true;
+    }
+}
+
+fn create_added_event(
+    name: &str,
+    date: DateTime<Tz>,
+    addition: &Addition,
+    language: Language,
+    warnings: &mut Vec<String>,
+) -> SoonToBeIcsEvent {
+    let title = addition.title.as_deref().unwrap_or(name);
+    SoonToBeIcsEvent {
+        pretty_name: addition.namesuffix.as_ref().map_or_else(
+            || title.to_owned(),
+            |namesuffix| format!("{title} {namesuffix}"),
+        ),
+        name: name.to_owned(),
+        source: ADDED_EVENT_SOURCE.to_owned(),
+        original_start_time: date,
+        status: EventStatus::Confirmed,
+        start_time: addition
+            .starttime
+            .map_or(date, |time| on_same_day(date, time, name, warnings)),
+        end_time: on_same_day(date, addition.endtime, name, warnings),
+        alerts: addition.alerts.clone(),
+        description: addition
+            .description
+            .clone()
+            .unwrap_or_else(|| messages::added_event_description(language).to_owned()),
+        location: addition.room.clone().unwrap_or_default(),
+        categories: Vec::new(),
+        color: None,
+        transparent: false,
+    }
+}
+
+fn apply_modification(
+    event: &mut SoonToBeIcsEvent,
+    modification: &Modification,
+    warnings: &mut Vec<String>,
+) {
+    let date = event.start_time;
+
+    if let Some(namesuffix) = &modification.namesuffix {
+        event.pretty_name = format!("{} {namesuffix}", event.pretty_name);
+    }
 
-        if let Some(time) = change.starttime {
-            event.start_time = change.date.date().and_time(time);
+    if let Some(room) = &modification.room {
+        event.location.clone_from(room);
+    }
+
+    if let Some(alerts) = &modification.alerts {
+        event.alerts = Some(alerts.clone());
+    }
+
+    if let Some(time) = modification.starttime {
+        event.start_time = on_same_day(date, time, &event.name, warnings);
+    }
+
+    if let Some(time) = modification.endtime {
+        event.end_time = on_same_day(date, time, &event.name, warnings);
+    }
+}
+
+/// Another time on the same local day
+fn on_same_day(
+    date: DateTime<Tz>,
+    time: NaiveTime,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> DateTime<Tz> {
+    let (result, warning) = local_time::resolve(date.timezone(), date.date_naive().and_time(time));
+    if let Some(warning) = warning {
+        warnings.push(format!("change of {name}: {warning}"));
+    }
+    result
+}
+
+/// Index of the event each change is meant for and whether it only matched within the tolerance.
+///
+/// Exact matches are claimed first so a change of a moved event can not take the event of another change.
+/// Exact matches prefer an event no other change is meant for (like duplicates in the eventfile)
+/// but may share one. Fallback matches only use events no other change is meant for.
+/// Added and invalid changes have no target.
+fn find_events(
+    events: &[SoonToBeIcsEvent],
+    changes: &[(String, Change)],
+    tolerance: TimeDelta,
+    timezone: Tz,
+) -> Vec<Option<(usize, bool)>> {
+    let mut claimed = vec![false; events.len()];
+    let mut targets = vec![None; changes.len()];
+    let is_added =
+        |change: &Change| matches!(change.kind, ChangeKind::Add(_) | ChangeKind::Invalid(_));
+
+    for (target, (name, change)) in targets.iter_mut().zip(changes) {
+        if is_added(change) {
+            continue;
+        }
+        let is_exact = |(_, event): &(usize, &SoonToBeIcsEvent)| {
+            event.name == *name && event.start_time == change.date
+        };
+        let exact = unclaimed(events, &claimed, name)
+            .find(is_exact)
+            .or_else(|| events.iter().enumerate().find(is_exact));
+        if let Some((i, _)) = exact {
+            claimed[i] = true;
+            *target = Some((i, false));
         }
+    }
 
-        if let Some(time) = change.endtime {
-            event.end_time = change.date.date().and_time(time);
+    for (target, (name, change)) in targets.iter_mut().zip(changes) {
+        if target.is_some() || is_added(change) {
+            continue;
+        }
+        let date = change.date.with_timezone(&timezone);
+        let closest = unclaimed(events, &claimed, name)
+            .filter(|(_, event)| {
+                event.start_time.with_timezone(&timezone).date_naive() == date.date_naive()
+            })
+            .map(|(i, event)| (i, (event.start_time - date).abs()))
+            .filter(|(_, distance)| *distance <= tolerance)
+            .min_by_key(|(_, distance)| *distance);
+        if let Some((i, _)) = closest {
+            claimed[i] = true;
+            *target = Some((i, true));
         }
-    } else {
-        // Event for this change doesnt exist.
-        // This not nice but the TelegramBot has to solve this via user feedback.
     }
 
-    Ok(())
+    targets
+}
+
+/// Events of the name which no change is meant for yet
+fn unclaimed<'a>(
+    events: &'a [SoonToBeIcsEvent],
+    claimed: &'a [bool],
+    name: &'a str,
+) -> impl Iterator<Item = (usize, &'a SoonToBeIcsEvent)> {
+    events
+        .iter()
+        .enumerate()
+        .filter(move |(i, event)| !claimed[*i] && event.name == name)
+}
+
+/// Changes whose event kept its name
+#[cfg(test)]
+fn unrenamed(changes: Vec<Change>) -> Vec<(String, Change)> {
+    changes
+        .into_iter()
+        .map(|change| (change.name.clone(), change))
+        .collect()
 }
 
 #[cfg(test)]
@@ -85,55 +270,88 @@ fn generate_events() -> Vec<SoonToBeIcsEvent> {
         SoonToBeIcsEvent {
             name: "BTI5-VSP/01".to_owned(),
             pretty_name: "BTI5-VSP/01".to_owned(),
+            source: "BTI5-VSP-01".to_owned(),
+            original_start_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+                .unwrap()
+                .and_hms_opt(8, 15, 0)
+                .unwrap()
+                .and_local_timezone(chrono_tz::UTC)
+                .unwrap(),
             status: EventStatus::Confirmed,
             start_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
                 .unwrap()
                 .and_hms_opt(8, 15, 0)
+                .unwrap()
+                .and_local_timezone(chrono_tz::UTC)
                 .unwrap(),
             end_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
                 .unwrap()
                 .and_hms_opt(11, 15, 0)
+                .unwrap()
+                .and_local_timezone(chrono_tz::UTC)
                 .unwrap(),
-            alert_minutes_before: None,
+            alerts: None,
             description: String::new(),
             location: String::new(),
+            categories: Vec::new(),
+            color: None,
+            transparent: false,
         },
         SoonToBeIcsEvent {
             name: "BTI5-VSP/01".to_owned(),
             pretty_name: "BTI5-VSP/01".to_owned(),
+            source: "BTI5-VSP-01".to_owned(),
+            original_start_time: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
+                .unwrap()
+                .and_hms_opt(8, 15, 0)
+                .unwrap()
+                .and_local_timezone(chrono_tz::UTC)
+                .unwrap(),
             status: EventStatus::Confirmed,
             start_time: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
                 .unwrap()
                 .and_hms_opt(8, 15, 0)
+                .unwrap()
+                .and_local_timezone(chrono_tz::UTC)
                 .unwrap(),
             end_time: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
                 .unwrap()
                 .and_hms_opt(11, 15, 0)
+                .unwrap()
+                .and_local_timezone(chrono_tz::UTC)
                 .unwrap(),
-            alert_minutes_before: None,
+            alerts: None,
             description: String::new(),
             location: String::new(),
+            categories: Vec::new(),
+            color: None,
+            transparent: false,
         },
     ]
 }
 
 #[test]
-fn non_existing_event_of_change_is_skipped() {
+fn non_existing_event_of_change_is_unmatched() {
     let mut events = generate_events();
     let change = Change {
         name: "BTI5-VS".to_owned(),
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 15)
             .unwrap()
             .and_hms_opt(13, 37, 0)
-            .unwrap(),
-        add: false,
-        remove: true,
-        starttime: None,
-        endtime: None,
-        namesuffix: None,
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Remove(Modification::default()),
     };
-    apply_change(&mut events, change, RemovedEvents::Cancelled).unwrap();
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.unmatched[0].name, "BTI5-VS");
     assert_eq!(events.len(), 2);
 
     let expected = generate_events();
@@ -149,15 +367,19 @@ fn remove_event_is_removed_completly() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 15, 0)
-            .unwrap(),
-        add: false,
-        remove: true,
-        starttime: None,
-        endtime: None,
-        namesuffix: None,
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Remove(Modification::default()),
     };
-    apply_change(&mut events, change, RemovedEvents::Removed).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::Removed,
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(events.len(), 1);
 }
 
@@ -169,15 +391,19 @@ fn remove_event_gets_marked_as_cancelled() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 15, 0)
-            .unwrap(),
-        add: false,
-        remove: true,
-        starttime: None,
-        endtime: None,
-        namesuffix: None,
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Remove(Modification::default()),
     };
-    apply_change(&mut events, change, RemovedEvents::Cancelled).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(events.len(), 2);
     assert_eq!(events[1].status, EventStatus::Cancelled);
 }
@@ -190,19 +416,58 @@ fn remove_event_gets_emoji_prefix() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 15, 0)
-            .unwrap(),
-        add: false,
-        remove: true,
-        starttime: None,
-        endtime: None,
-        namesuffix: None,
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Remove(Modification::default()),
     };
-    apply_change(&mut events, change, RemovedEvents::Emoji).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::Marked(RemovedEventMarks {
+            emoji: true,
+            ..RemovedEventMarks::default()
+        }),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(events.len(), 2);
     assert_eq!(events[1].pretty_name, "🚫 BTI5-VSP/01");
 }
 
+#[test]
+fn remove_event_gets_combined_marks() {
+    let mut events = generate_events();
+    let change = Change {
+        name: "BTI5-VSP/01".to_owned(),
+        date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
+            .unwrap()
+            .and_hms_opt(8, 15, 0)
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Remove(Modification::default()),
+    };
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::Marked(RemovedEventMarks {
+            cancelled: true,
+            emoji: true,
+            prefix: Some("[ENTFÄLLT]".to_owned()),
+            transparent: true,
+        }),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(events[1].status, EventStatus::Cancelled);
+    assert_eq!(events[1].pretty_name, "🚫 [ENTFÄLLT] BTI5-VSP/01");
+    assert!(events[1].transparent);
+    assert!(!events[0].transparent);
+}
+
 #[test]
 fn namesuffix_is_added() {
     let mut events = generate_events();
@@ -211,15 +476,22 @@ fn namesuffix_is_added() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 15, 0)
-            .unwrap(),
-        add: false,
-        remove: false,
-        starttime: None,
-        endtime: None,
-        namesuffix: Some("whatever".to_owned()),
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Modify(Modification {
+            namesuffix: Some("whatever".to_owned()),
+            ..Modification::default()
+        }),
     };
-    apply_change(&mut events, change, RemovedEvents::Cancelled).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(events[1].pretty_name, "BTI5-VSP/01 whatever");
 }
 
@@ -231,15 +503,22 @@ fn room_is_overwritten() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 15, 0)
-            .unwrap(),
-        add: false,
-        remove: false,
-        starttime: None,
-        endtime: None,
-        namesuffix: None,
-        room: Some("whereever".to_owned()),
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Modify(Modification {
+            room: Some("whereever".to_owned()),
+            ..Modification::default()
+        }),
     };
-    apply_change(&mut events, change, RemovedEvents::Cancelled).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(events[1].location, "whereever");
 }
 
@@ -251,17 +530,24 @@ fn starttime_changed() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 15, 0)
-            .unwrap(),
-        add: false,
-        remove: false,
-        starttime: Some(chrono::NaiveTime::from_hms_opt(8, 30, 0).unwrap()),
-        endtime: None,
-        namesuffix: None,
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Modify(Modification {
+            starttime: Some(NaiveTime::from_hms_opt(8, 30, 0).unwrap()),
+            ..Modification::default()
+        }),
     };
-    apply_change(&mut events, change, RemovedEvents::Cancelled).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(
-        events[1].start_time,
+        events[1].start_time.naive_local(),
         chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 30, 0)
@@ -277,17 +563,24 @@ fn endtime_changed() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 15, 0)
-            .unwrap(),
-        add: false,
-        remove: false,
-        starttime: None,
-        endtime: Some(chrono::NaiveTime::from_hms_opt(8, 30, 0).unwrap()),
-        namesuffix: None,
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Modify(Modification {
+            endtime: Some(NaiveTime::from_hms_opt(8, 30, 0).unwrap()),
+            ..Modification::default()
+        }),
     };
-    apply_change(&mut events, change, RemovedEvents::Cancelled).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(
-        events[1].end_time,
+        events[1].end_time.naive_local(),
         chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
             .unwrap()
             .and_hms_opt(8, 30, 0)
@@ -303,26 +596,38 @@ fn event_added() {
         date: chrono::NaiveDate::from_ymd_opt(2020, 5, 30)
             .unwrap()
             .and_hms_opt(10, 0, 0)
-            .unwrap(),
-        add: true,
-        remove: false,
-        starttime: None,
-        endtime: Some(chrono::NaiveTime::from_hms_opt(10, 30, 0).unwrap()),
-        namesuffix: None,
-        room: None,
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Add(Addition {
+            starttime: None,
+            endtime: NaiveTime::from_hms_opt(10, 30, 0).unwrap(),
+            title: None,
+            namesuffix: None,
+            room: None,
+            description: None,
+            alerts: None,
+        }),
     };
-    apply_change(&mut events, change, RemovedEvents::Cancelled).unwrap();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
     assert_eq!(events.len(), 3);
     assert_eq!(events[2].name, "BTI5-VSP/01");
     assert_eq!(
-        events[2].start_time,
+        events[2].start_time.naive_local(),
         chrono::NaiveDate::from_ymd_opt(2020, 5, 30)
             .unwrap()
             .and_hms_opt(10, 0, 0)
             .unwrap()
     );
     assert_eq!(
-        events[2].end_time,
+        events[2].end_time.naive_local(),
         chrono::NaiveDate::from_ymd_opt(2020, 5, 30)
             .unwrap()
             .and_hms_opt(10, 30, 0)
@@ -330,3 +635,398 @@ fn event_added() {
     );
     assert_eq!(events[2].location, "");
 }
+
+#[test]
+fn added_event_description_is_in_language_of_user() {
+    let mut events = Vec::new();
+    let change = Change {
+        name: "Extra".to_owned(),
+        date: chrono::NaiveDate::from_ymd_opt(2020, 5, 30)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Add(Addition {
+            starttime: None,
+            endtime: NaiveTime::from_hms_opt(10, 30, 0).unwrap(),
+            title: None,
+            namesuffix: None,
+            room: None,
+            description: None,
+            alerts: None,
+        }),
+    };
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::English,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(
+        events[0].description,
+        "This is an additional event which you added manually via the Telegram bot."
+    );
+}
+
+#[test]
+fn event_added_with_own_start_title_and_description() {
+    let mut events = generate_events();
+    let change = Change {
+        name: "BTI5-VSP/01".to_owned(),
+        date: chrono::NaiveDate::from_ymd_opt(2020, 5, 30)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc(),
+        kind: ChangeKind::Add(Addition {
+            starttime: Some(NaiveTime::from_hms_opt(10, 0, 0).unwrap()),
+            endtime: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            title: Some("Nachklausur".to_owned()),
+            namesuffix: Some("(2. Termin)".to_owned()),
+            room: None,
+            description: Some("Bring a pen".to_owned()),
+            alerts: None,
+        }),
+    };
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    let added = &events[2];
+    assert_eq!(added.name, "BTI5-VSP/01");
+    assert_eq!(added.pretty_name, "Nachklausur (2. Termin)");
+    assert_eq!(added.description, "Bring a pen");
+    assert_eq!(
+        added.original_start_time.naive_local(),
+        chrono::NaiveDate::from_ymd_opt(2020, 5, 30)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    );
+    assert_eq!(
+        added.start_time.naive_local(),
+        chrono::NaiveDate::from_ymd_opt(2020, 5, 30)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+    );
+
+    let details = crate::userconfig::EventDetails {
+        alert_minutes_before: Some(30),
+        notes: Some("Room is announced by mail".to_owned()),
+        ..crate::userconfig::EventDetails::default()
+    };
+    crate::apply_details::apply_details(&mut events[2], &details);
+    assert_eq!(
+        events[2].description,
+        "Bring a pen\n\nRoom is announced by mail"
+    );
+    assert_eq!(events[2].alerts.as_ref().map(Vec::len), Some(1));
+}
+
+#[cfg(test)]
+fn create_moved_room_change(minutes: i64) -> Change {
+    Change {
+        name: "BTI5-VSP/01".to_owned(),
+        date: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+            .unwrap()
+            .and_hms_opt(8, 15, 0)
+            .unwrap()
+            .and_utc()
+            + TimeDelta::minutes(minutes),
+        kind: ChangeKind::Modify(Modification {
+            room: Some("1337".to_owned()),
+            ..Modification::default()
+        }),
+    }
+}
+
+#[test]
+fn invalid_change_is_unmatched_with_warning() {
+    let mut events = generate_events();
+    let invalid: Change =
+        serde_json::from_str(r#"{"name": "BTI5-VSP/01", "date": "2020-04-02T08:15"}"#).unwrap();
+    let mut warnings = Vec::new();
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![invalid.clone(), create_moved_room_change(0)]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut warnings,
+    );
+    assert_eq!(mismatches.unmatched, [invalid]);
+    assert_eq!(
+        warnings,
+        [
+            "change of BTI5-VSP/01 at 2020-04-02T08:15 does nothing, it should add, remove or modify the event"
+        ]
+    );
+    assert_eq!(events[0].location, "1337");
+}
+
+#[test]
+fn moved_event_is_not_matched_strictly() {
+    let mut events = generate_events();
+    let change = create_moved_room_change(15);
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.unmatched.len(), 1);
+    assert_eq!(events, generate_events());
+}
+
+#[test]
+fn moved_event_is_matched_within_tolerance() {
+    let mut events = generate_events();
+    let change = create_moved_room_change(-15);
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::minutes(30),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.fuzzy.len(), 1);
+    assert_eq!(events[0].location, "1337");
+}
+
+#[test]
+fn renamed_event_is_reported_with_old_name() {
+    let mut events = generate_events();
+    let mut change = create_moved_room_change(-15);
+    change.name = "BTI5-VSP".to_owned();
+    let mismatches = apply_changes(
+        &mut events,
+        vec![("BTI5-VSP/01".to_owned(), change)],
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::minutes(30),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.fuzzy[0].name, "BTI5-VSP");
+    assert_eq!(events[0].location, "1337");
+}
+
+#[test]
+fn moved_event_outside_tolerance_is_unmatched() {
+    let mut events = generate_events();
+    let change = create_moved_room_change(45);
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::minutes(30),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.unmatched.len(), 1);
+    assert_eq!(events, generate_events());
+}
+
+#[test]
+fn fuzzy_change_does_not_take_event_of_exact_change() {
+    let mut events = generate_events();
+    let mut fuzzy = create_moved_room_change(10);
+    fuzzy.kind = ChangeKind::Modify(Modification {
+        namesuffix: Some("fuzzy".to_owned()),
+        ..Modification::default()
+    });
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![fuzzy, create_moved_room_change(0)]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::minutes(30),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.unmatched.len(), 1);
+    assert!(mismatches.fuzzy.is_empty());
+    assert_eq!(events[0].location, "1337");
+    assert_eq!(events[0].pretty_name, "BTI5-VSP/01");
+}
+
+#[test]
+fn two_fuzzy_changes_do_not_match_the_same_event() {
+    let mut events = generate_events();
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![
+            create_moved_room_change(10),
+            create_moved_room_change(10),
+        ]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::minutes(30),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.unmatched.len(), 1);
+    assert_eq!(mismatches.fuzzy.len(), 1);
+}
+
+#[test]
+fn exact_changes_apply_to_the_same_event() {
+    let mut events = generate_events();
+    let mut remove = create_moved_room_change(0);
+    remove.kind = ChangeKind::Remove(Modification::default());
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![create_moved_room_change(0), remove.clone(), remove]),
+        Language::German,
+        &RemovedEvents::Removed,
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert!(mismatches.unmatched.is_empty());
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0], generate_events()[1]);
+}
+
+#[test]
+fn fuzzy_match_stays_on_the_same_day() {
+    let mut events = generate_events();
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![create_moved_room_change(-9 * 60)]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::hours(12),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert_eq!(mismatches.unmatched.len(), 1);
+    assert_eq!(events, generate_events());
+}
+
+#[test]
+fn exact_match_is_preferred_over_fuzzy() {
+    let mut events = generate_events();
+    let mut closer = events[0].clone();
+    closer.start_time += TimeDelta::minutes(1);
+    closer.location = "closer".to_owned();
+    events.push(closer);
+
+    let change = create_moved_room_change(0);
+    let mismatches = apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::minutes(30),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    assert!(mismatches.unmatched.is_empty());
+    assert!(mismatches.fuzzy.is_empty());
+    assert_eq!(events[0].location, "1337");
+    assert_eq!(events[2].location, "closer");
+}
+
+#[test]
+fn starttime_in_dst_gap_is_warned_about() {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 3, 29)
+        .unwrap()
+        .and_hms_opt(1, 0, 0)
+        .unwrap()
+        .and_local_timezone(chrono_tz::Europe::Berlin)
+        .unwrap();
+    let mut events = generate_events();
+    events[0].start_time = start_time;
+    events[0].end_time = start_time + TimeDelta::hours(3);
+
+    let change = Change {
+        name: "BTI5-VSP/01".to_owned(),
+        date: start_time.to_utc(),
+        kind: ChangeKind::Modify(Modification {
+            starttime: Some(NaiveTime::from_hms_opt(2, 30, 0).unwrap()),
+            ..Modification::default()
+        }),
+    };
+    let mut warnings = Vec::new();
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::Europe::Berlin,
+        &mut warnings,
+    );
+    assert_eq!(
+        events[0].start_time.to_rfc3339(),
+        "2020-03-29T03:30:00+02:00"
+    );
+    assert_eq!(
+        warnings,
+        [
+            "change of BTI5-VSP/01: 2020-03-29 02:30 does not exist in Europe/Berlin, 2020-03-29 03:30 is used"
+        ]
+    );
+}
+
+#[test]
+fn change_matches_event_by_instant() {
+    let mut events = generate_events();
+    let change = create_moved_room_change(0);
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::Europe::Berlin,
+        &mut Vec::new(),
+    );
+    assert_eq!(events[0].location, "1337");
+}
+
+#[test]
+fn alerts_of_occurrence_are_replaced() {
+    let mut events = generate_events();
+    let change = Change {
+        kind: ChangeKind::Modify(Modification {
+            alerts: Some(vec![crate::userconfig::Alert {
+                minutes_before: 1440,
+                action: crate::userconfig::AlarmAction::Display,
+                description: Some("Klausur".to_owned()),
+            }]),
+            ..Modification::default()
+        }),
+        ..create_moved_room_change(0)
+    };
+    apply_changes(
+        &mut events,
+        unrenamed(vec![change]),
+        Language::German,
+        &RemovedEvents::default(),
+        TimeDelta::zero(),
+        chrono_tz::UTC,
+        &mut Vec::new(),
+    );
+    let alerts = events[0].alerts.as_ref().unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].minutes_before, 1440);
+    assert_eq!(events[1].alerts, None);
+}
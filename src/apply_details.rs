@@ -2,7 +2,19 @@ use crate::generate_ics::SoonToBeIcsEvent;
 use crate::userconfig::EventDetails;
 
 pub fn apply_details(event: &mut SoonToBeIcsEvent, details: &EventDetails) {
-    event.alert_minutes_before = details.alert_minutes_before;
+    if let Some(title) = render_title(event, details) {
+        // Keep what changes added around the name like the emoji of removed events
+        event.pretty_name = event.pretty_name.replacen(&event.name, &title, 1);
+    }
+
+    if event.alerts.is_none() {
+        event.alerts = Some(details.alerts());
+    }
+
+    event.categories.clone_from(&details.categories);
+    event.color.clone_from(&details.color);
+    // Removed events might already be transparent
+    event.transparent |= details.transparent;
 
     if let Some(notes) = &details.notes
         && !notes.is_empty()
@@ -15,23 +27,71 @@ pub fn apply_details(event: &mut SoonToBeIcsEvent, details: &EventDetails) {
     }
 }
 
+fn render_title(event: &SoonToBeIcsEvent, details: &EventDetails) -> Option<String> {
+    let template = details
+        .title
+        .as_deref()
+        .or_else(|| details.alias.as_ref().map(|_| "{alias}"))?;
+    let alias = details.alias.as_deref().unwrap_or(&event.name);
+
+    // Single pass so placeholders within the inserted values stay as they are
+    let mut title = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        title += &rest[..start];
+        rest = &rest[start..];
+        let placeholder = rest.find('}').and_then(|end| {
+            let value: &str = match &rest[1..end] {
+                "name" => &event.name,
+                "alias" => alias,
+                "location" => &event.location,
+                _ => return None,
+            };
+            Some((end + 1, value))
+        });
+        if let Some((length, value)) = placeholder {
+            title += value;
+            rest = &rest[length..];
+        } else {
+            title.push('{');
+            rest = &rest[1..];
+        }
+    }
+    title += rest;
+    Some(title)
+}
+
 #[cfg(test)]
 fn create_event(description: &str) -> SoonToBeIcsEvent {
     SoonToBeIcsEvent {
         name: "BTI5-VSP/01".to_owned(),
         pretty_name: "BTI5-VSP/01".to_owned(),
+        source: "BTI5-VSP-01".to_owned(),
+        original_start_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+            .unwrap()
+            .and_hms_opt(8, 15, 0)
+            .unwrap()
+            .and_local_timezone(chrono_tz::UTC)
+            .unwrap(),
         status: crate::generate_ics::EventStatus::Confirmed,
         start_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
             .unwrap()
             .and_hms_opt(8, 15, 0)
+            .unwrap()
+            .and_local_timezone(chrono_tz::UTC)
             .unwrap(),
         end_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
             .unwrap()
             .and_hms_opt(11, 15, 0)
+            .unwrap()
+            .and_local_timezone(chrono_tz::UTC)
             .unwrap(),
-        alert_minutes_before: None,
+        alerts: None,
         description: description.to_owned(),
         location: String::new(),
+        categories: Vec::new(),
+        color: None,
+        transparent: false,
     }
 }
 
@@ -40,10 +100,20 @@ fn check_alert(alert_minutes_before: Option<u16>) {
     let details = EventDetails {
         alert_minutes_before,
         notes: None,
+        ..EventDetails::default()
     };
     let mut event = create_event("");
     apply_details(&mut event, &details);
-    assert_eq!(event.alert_minutes_before, alert_minutes_before);
+    let alerts = event.alerts.unwrap();
+    assert_eq!(
+        alerts.iter().map(|alert| alert.minutes_before).next(),
+        alert_minutes_before
+    );
+    assert!(
+        alerts
+            .iter()
+            .all(|alert| alert.action == crate::userconfig::AlarmAction::Audio)
+    );
 }
 
 #[test]
@@ -54,11 +124,45 @@ fn alert_examples() {
     check_alert(Some(60));
 }
 
+#[test]
+fn multiple_alerts() {
+    let details = EventDetails {
+        alert_minutes_before: Some(15),
+        alerts: vec![crate::userconfig::Alert {
+            minutes_before: 1440,
+            action: crate::userconfig::AlarmAction::Display,
+            description: None,
+        }],
+        notes: None,
+        ..EventDetails::default()
+    };
+    let mut event = create_event("");
+    apply_details(&mut event, &details);
+    let alerts = event.alerts.unwrap();
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(alerts[0].minutes_before, 15);
+    assert_eq!(alerts[1].minutes_before, 1440);
+}
+
+#[test]
+fn alerts_of_change_are_kept() {
+    let details = EventDetails {
+        alert_minutes_before: Some(15),
+        notes: None,
+        ..EventDetails::default()
+    };
+    let mut event = create_event("");
+    event.alerts = Some(Vec::new());
+    apply_details(&mut event, &details);
+    assert_eq!(event.alerts, Some(Vec::new()));
+}
+
 #[cfg(test)]
 fn check_description(notes: Option<&str>, event_description: &str, expected: &str) {
     let details = EventDetails {
         alert_minutes_before: None,
         notes: notes.map(ToOwned::to_owned),
+        ..EventDetails::default()
     };
     let mut event = create_event(event_description);
     apply_details(&mut event, &details);
@@ -84,3 +188,71 @@ fn some_note_no_description() {
 fn some_note_some_description() {
     check_description(Some("foo"), "bar", "bar\n\nfoo");
 }
+
+#[test]
+fn categories_color_and_transparency() {
+    let details = EventDetails {
+        categories: vec!["tutorial".to_owned()],
+        color: Some("turquoise".to_owned()),
+        transparent: true,
+        ..EventDetails::default()
+    };
+    let mut event = create_event("");
+    apply_details(&mut event, &details);
+    assert_eq!(event.categories, ["tutorial"]);
+    assert_eq!(event.color.as_deref(), Some("turquoise"));
+    assert!(event.transparent);
+}
+
+#[cfg(test)]
+fn check_title(alias: Option<&str>, title: Option<&str>, pretty_name: &str, expected: &str) {
+    let details = EventDetails {
+        alias: alias.map(ToOwned::to_owned),
+        title: title.map(ToOwned::to_owned),
+        ..EventDetails::default()
+    };
+    let mut event = create_event("");
+    event.location = "1060".to_owned();
+    event.pretty_name = pretty_name.to_owned();
+    apply_details(&mut event, &details);
+    assert_eq!(event.pretty_name, expected);
+}
+
+#[test]
+fn title_examples() {
+    check_title(None, None, "BTI5-VSP/01", "BTI5-VSP/01");
+    check_title(Some("VS Praktikum"), None, "BTI5-VSP/01", "VS Praktikum");
+    check_title(
+        Some("VS Praktikum"),
+        Some("{alias} ({location})"),
+        "BTI5-VSP/01",
+        "VS Praktikum (1060)",
+    );
+    check_title(
+        None,
+        Some("{name} in {location}"),
+        "BTI5-VSP/01",
+        "BTI5-VSP/01 in 1060",
+    );
+}
+
+#[test]
+fn title_placeholders_are_only_expanded_in_the_template() {
+    check_title(Some("{location}"), None, "BTI5-VSP/01", "{location}");
+    check_title(
+        Some("VS {name}"),
+        Some("{alias} {unknown} {location"),
+        "BTI5-VSP/01",
+        "VS {name} {unknown} {location",
+    );
+}
+
+#[test]
+fn title_keeps_additions_of_changes() {
+    check_title(
+        Some("VS Praktikum"),
+        None,
+        "🚫 BTI5-VSP/01 Klausur",
+        "🚫 VS Praktikum Klausur",
+    );
+}
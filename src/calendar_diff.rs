@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+
+use crate::ics_writer::unfold_lines;
+use crate::revisions::vevent_key;
+
+/// VEVENTs which differ between two builds of a calendar.
+///
+/// VEVENTs are matched by their UID (and RECURRENCE-ID).
+/// SEQUENCE and LAST-MODIFIED are ignored as they only follow the other content lines.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CalendarDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+struct Vevent {
+    label: String,
+    content: String,
+}
+
+impl CalendarDiff {
+    pub fn new(before: &str, after: &str) -> Self {
+        let mut before = vevents(before);
+        let mut diff = Self::default();
+
+        for (key, event) in vevents(after) {
+            match before.remove(&key) {
+                Some(previous) if previous.content == event.content => {}
+                Some(_) => diff.modified.push(event.label),
+                None => diff.added.push(event.label),
+            }
+        }
+        diff.removed = before.into_values().map(|event| event.label).collect();
+
+        diff.added.sort();
+        diff.removed.sort();
+        diff.modified.sort();
+        diff
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+pub fn write_calendar_diff<W: std::io::Write>(
+    target: &mut W,
+    name: &str,
+    diff: &CalendarDiff,
+) -> std::io::Result<()> {
+    writeln!(
+        target,
+        "{name}: {} added, {} removed, {} modified",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.modified.len()
+    )?;
+    for label in &diff.added {
+        writeln!(target, "  + {label}")?;
+    }
+    for label in &diff.removed {
+        writeln!(target, "  - {label}")?;
+    }
+    for label in &diff.modified {
+        writeln!(target, "  ~ {label}")?;
+    }
+    Ok(())
+}
+
+fn vevents(ics: &str) -> BTreeMap<String, Vevent> {
+    let mut result = BTreeMap::new();
+    let mut content: Option<String> = None;
+    for line in unfold_lines(ics) {
+        if line == "BEGIN:VEVENT" {
+            content = Some(String::new());
+        } else if line == "END:VEVENT" {
+            if let Some(content) = content.take()
+                && let Some(key) = vevent_key(&content)
+            {
+                let label = label(&content);
+                result.insert(key, Vevent { label, content });
+            }
+        } else if let Some(content) = &mut content
+            && !line.starts_with("SEQUENCE:")
+            && !line.starts_with("LAST-MODIFIED:")
+        {
+            *content += &line;
+            *content += "\n";
+        }
+    }
+    result
+}
+
+/// Human readable start and summary of the VEVENT like `20200409T081500 BTI5-VS`
+fn label(content: &str) -> String {
+    let value_of = |name: &str| {
+        content
+            .lines()
+            .find(|line| {
+                line.strip_prefix(name)
+                    .is_some_and(|rest| rest.starts_with([':', ';']))
+            })
+            .and_then(|line| line.split_once(':'))
+            .map_or("", |(_, value)| value)
+    };
+    format!("{} {}", value_of("DTSTART"), value_of("SUMMARY"))
+}
+
+#[cfg(test)]
+fn build_ics(vevents: &[(&str, &str, &str)]) -> String {
+    use std::fmt::Write as _;
+
+    let mut ics = String::from("BEGIN:VCALENDAR\r\n");
+    for (uid, start, summary) in vevents {
+        _ = write!(
+            ics,
+            "BEGIN:VEVENT\r\nUID:{uid}\r\nDTSTART;TZID=Europe/Berlin:{start}\r\nSUMMARY:{summary}\r\nSEQUENCE:0\r\nEND:VEVENT\r\n"
+        );
+    }
+    ics += "END:VCALENDAR\r\n";
+    ics
+}
+
+#[test]
+fn same_calendar_has_empty_diff() {
+    let ics = build_ics(&[("a", "20200409T081500", "VS")]);
+    assert!(CalendarDiff::new(&ics, &ics).is_empty());
+}
+
+#[test]
+fn diff_by_uid() {
+    let before = build_ics(&[
+        ("a", "20200409T081500", "VS"),
+        ("b", "20200410T081500", "MA"),
+    ]);
+    let after = build_ics(&[
+        ("a", "20200409T091500", "VS"),
+        ("c", "20200411T081500", "SE"),
+    ]);
+    assert_eq!(
+        CalendarDiff::new(&before, &after),
+        CalendarDiff {
+            added: vec!["20200411T081500 SE".to_owned()],
+            removed: vec!["20200410T081500 MA".to_owned()],
+            modified: vec!["20200409T091500 VS".to_owned()],
+        }
+    );
+}
+
+#[test]
+fn revision_lines_are_ignored() {
+    let before = build_ics(&[("a", "20200409T081500", "VS")]);
+    let after = before.replace("SEQUENCE:0", "SEQUENCE:1");
+    assert!(CalendarDiff::new(&before, &after).is_empty());
+}
+
+#[test]
+fn overrides_are_compared_on_their_own() {
+    let before = build_ics(&[("a", "20200409T081500", "VS")]);
+    let after = before.replace(
+        "END:VCALENDAR",
+        "BEGIN:VEVENT\r\nUID:a\r\nRECURRENCE-ID;TZID=Europe/Berlin:20200416T081500\r\nDTSTART;TZID=Europe/Berlin:20200416T091500\r\nSUMMARY:VS\r\nEND:VEVENT\r\nEND:VCALENDAR",
+    );
+    let diff = CalendarDiff::new(&before, &after);
+    assert_eq!(diff.added, ["20200416T091500 VS"]);
+    assert!(diff.removed.is_empty());
+    assert!(diff.modified.is_empty());
+}
+
+#[test]
+fn diff_output() {
+    let diff = CalendarDiff {
+        added: vec!["20200411T081500 SE".to_owned()],
+        removed: vec![],
+        modified: vec!["20200409T091500 VS".to_owned()],
+    };
+    let mut result = Vec::new();
+    write_calendar_diff(&mut result, "Peter", &diff).unwrap();
+    assert_eq!(
+        String::from_utf8(result).unwrap(),
+        "Peter: 1 added, 0 removed, 1 modified\n  + 20200411T081500 SE\n  ~ 20200409T091500 VS\n"
+    );
+}
@@ -1,6 +1,15 @@
 use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::Path;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::userconfig::Change;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Changetype {
     Added,
     Changed,
@@ -8,6 +17,7 @@ pub enum Changetype {
     Removed,
     Same,
     Skipped,
+    Failed,
 }
 
 impl Changetype {
@@ -18,9 +28,15 @@ impl Changetype {
         Self::Removed,
         Self::Same,
         Self::Skipped,
+        Self::Failed,
+    ];
+    pub const INTERESTING: &'static [Self] = &[
+        Self::Added,
+        Self::Changed,
+        Self::Moved,
+        Self::Removed,
+        Self::Failed,
     ];
-    pub const INTERESTING: &'static [Self] =
-        &[Self::Added, Self::Changed, Self::Moved, Self::Removed];
 
     const fn as_str(self) -> &'static str {
         match self {
@@ -30,23 +46,67 @@ impl Changetype {
             Self::Removed => "removed",
             Self::Same => "same",
             Self::Skipped => "skipped",
+            Self::Failed => "failed",
         }
     }
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Changestatus {
     pub name: String,
     pub changetype: Changetype,
+    /// Chat of the user when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<i64>,
+    /// Amount of events in the calendar
+    pub events: usize,
+    /// Eventfiles of the userconfig which could not be read
+    pub skipped_eventfiles: Vec<String>,
+    /// Changes of the userconfig whose event does not exist
+    pub unmatched_changes: Vec<Change>,
+    /// Changes applied to an event which moved a bit
+    pub fuzzy_matched_changes: Vec<Change>,
+    /// Old event names the userconfig still uses
+    pub renamed_events: Vec<String>,
+    /// Something looked wrong but the calendar was built anyway like an ambiguous local time
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl Changestatus {
+    pub const fn new(name: String, changetype: Changetype) -> Self {
+        Self {
+            name,
+            changetype,
+            chat_id: None,
+            events: 0,
+            skipped_eventfiles: Vec::new(),
+            unmatched_changes: Vec::new(),
+            fuzzy_matched_changes: Vec::new(),
+            renamed_events: Vec::new(),
+            warnings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn failed(name: String, chat_id: Option<i64>, error: &anyhow::Error) -> Self {
+        Self {
+            chat_id,
+            errors: vec![format!("{error:#}")],
+            ..Self::new(name, Changetype::Failed)
+        }
+    }
 }
 
 pub fn write_change_summary<W: std::io::Write>(
     target: &mut W,
-    changes: Vec<Changestatus>,
+    changes: &[Changestatus],
     to_be_shown: &[Changetype],
 ) -> std::io::Result<()> {
-    let mut map: HashMap<Changetype, Vec<String>> = HashMap::new();
+    let mut map: HashMap<Changetype, Vec<&str>> = HashMap::new();
     for change in changes {
-        map.entry(change.changetype).or_default().push(change.name);
+        map.entry(change.changetype).or_default().push(&change.name);
     }
     for key in to_be_shown {
         if let Some(val) = map.get_mut(key) {
@@ -58,47 +118,62 @@ pub fn write_change_summary<W: std::io::Write>(
     Ok(())
 }
 
+/// One JSON object per line for every change of a build run
+pub fn write_change_report<W: std::io::Write>(
+    target: &mut W,
+    changes: &[Changestatus],
+    time: DateTime<Utc>,
+) -> std::io::Result<()> {
+    #[derive(Serialize)]
+    struct Line<'a> {
+        time: DateTime<Utc>,
+        #[serde(flatten)]
+        change: &'a Changestatus,
+    }
+
+    for change in changes {
+        serde_json::to_writer(&mut *target, &Line { time, change })?;
+        writeln!(target)?;
+    }
+    Ok(())
+}
+
+pub fn append_change_report(
+    path: &Path,
+    changes: &[Changestatus],
+    time: DateTime<Utc>,
+) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut lines = Vec::new();
+    write_change_report(&mut lines, changes, time)?;
+    // A single write keeps the lines of concurrent runs apart
+    file.write_all(&lines)
+}
+
 #[cfg(test)]
 fn generate_every_type_once() -> Vec<Changestatus> {
     vec![
-        Changestatus {
-            name: String::from("A"),
-            changetype: Changetype::Added,
-        },
-        Changestatus {
-            name: String::from("C"),
-            changetype: Changetype::Changed,
-        },
-        Changestatus {
-            name: String::from("M"),
-            changetype: Changetype::Moved,
-        },
-        Changestatus {
-            name: String::from("R"),
-            changetype: Changetype::Removed,
-        },
-        Changestatus {
-            name: String::from("Sa"),
-            changetype: Changetype::Same,
-        },
-        Changestatus {
-            name: String::from("Sk"),
-            changetype: Changetype::Skipped,
-        },
+        Changestatus::new(String::from("A"), Changetype::Added),
+        Changestatus::new(String::from("C"), Changetype::Changed),
+        Changestatus::new(String::from("M"), Changetype::Moved),
+        Changestatus::new(String::from("R"), Changetype::Removed),
+        Changestatus::new(String::from("Sa"), Changetype::Same),
+        Changestatus::new(String::from("Sk"), Changetype::Skipped),
+        Changestatus::new(String::from("F"), Changetype::Failed),
     ]
 }
 
 #[test]
 fn summary_without_changes_is_empty() {
     let mut result = Vec::new();
-    write_change_summary(&mut result, vec![], Changetype::ALL).unwrap();
+    write_change_summary(&mut result, &[], Changetype::ALL).unwrap();
     assert_eq!(result, b"");
 }
 
 #[test]
 fn summary_shows_every_type_once() {
     let mut result = Vec::new();
-    write_change_summary(&mut result, generate_every_type_once(), Changetype::ALL).unwrap();
+    write_change_summary(&mut result, &generate_every_type_once(), Changetype::ALL).unwrap();
     assert_eq!(
         result,
         br#"added   (  1): ["A"]
@@ -107,6 +182,7 @@ moved   (  1): ["M"]
 removed (  1): ["R"]
 same    (  1): ["Sa"]
 skipped (  1): ["Sk"]
+failed  (  1): ["F"]
 "#
     );
 }
@@ -116,7 +192,7 @@ fn summary_shows_interesting_types_once() {
     let mut result = Vec::new();
     write_change_summary(
         &mut result,
-        generate_every_type_once(),
+        &generate_every_type_once(),
         Changetype::INTERESTING,
     )
     .unwrap();
@@ -126,6 +202,34 @@ fn summary_shows_interesting_types_once() {
 changed (  1): ["C"]
 moved   (  1): ["M"]
 removed (  1): ["R"]
+failed  (  1): ["F"]
+"#
+    );
+}
+
+#[test]
+fn report_has_one_line_per_change() {
+    let time = chrono::NaiveDate::from_ymd_opt(2020, 4, 9)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap()
+        .and_utc();
+    let mut changed = Changestatus::new(String::from("Peter"), Changetype::Changed);
+    changed.chat_id = Some(42);
+    changed.events = 3;
+    changed.skipped_eventfiles.push(String::from("BTI5-VS"));
+    let failed = Changestatus::failed(
+        String::from("1337.json"),
+        None,
+        &anyhow::anyhow!("invalid json"),
+    );
+
+    let mut result = Vec::new();
+    write_change_report(&mut result, &[changed, failed], time).unwrap();
+    assert_eq!(
+        String::from_utf8(result).unwrap(),
+        r#"{"time":"2020-04-09T08:15:00Z","name":"Peter","changetype":"changed","chatId":42,"events":3,"skippedEventfiles":["BTI5-VS"],"unmatchedChanges":[],"fuzzyMatchedChanges":[],"renamedEvents":[],"warnings":[],"errors":[]}
+{"time":"2020-04-09T08:15:00Z","name":"1337.json","changetype":"failed","events":0,"skippedEventfiles":[],"unmatchedChanges":[],"fuzzyMatchedChanges":[],"renamedEvents":[],"warnings":[],"errors":["invalid json"]}
 "#
     );
 }
@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand, ValueHint};
+
+use crate::userconfig::TimeFormat;
+
+#[derive(Debug, Parser)]
+#[command(about, version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Directory with the eventfiles created by the downloader
+    #[arg(
+        long,
+        global = true,
+        env = "EVENTFILES_DIR",
+        value_hint = ValueHint::DirPath,
+        default_value = "eventfiles"
+    )]
+    pub eventfiles_dir: PathBuf,
+
+    /// Directory with the userconfigs created by the Telegram Bot
+    #[arg(
+        long,
+        global = true,
+        env = "USERCONFIG_DIR",
+        value_hint = ValueHint::DirPath,
+        default_value = "userconfig"
+    )]
+    pub userconfig_dir: PathBuf,
+
+    /// Directory the generated calendars are written to
+    #[arg(
+        long,
+        global = true,
+        env = "CALENDARS_DIR",
+        value_hint = ValueHint::DirPath,
+        default_value = "calendars"
+    )]
+    pub calendars_dir: PathBuf,
+
+    /// Write nothing but report which events would be added, removed or modified in each calendar
+    #[arg(long, global = true, conflicts_with = "http_listen")]
+    pub dry_run: bool,
+
+    /// IANA zone of the eventfiles which is also the default zone of the calendars
+    #[arg(
+        long,
+        global = true,
+        env = "TIMEZONE",
+        value_name = "ZONE",
+        default_value = "Europe/Berlin"
+    )]
+    pub timezone: Tz,
+
+    /// How times are written into the calendars unless the userconfig says otherwise.
+    /// Some clients handle `utc` better than local times with a VTIMEZONE.
+    #[arg(
+        long,
+        global = true,
+        env = "TIME_FORMAT",
+        value_enum,
+        default_value_t = TimeFormat::Local
+    )]
+    pub time_format: TimeFormat,
+
+    /// Apply changes to the closest event of the same name on the same day starting within this many minutes when no event starts exactly at the change.
+    /// 0 only allows exact matches.
+    #[arg(
+        long,
+        global = true,
+        env = "CHANGE_TOLERANCE",
+        value_name = "MINUTES",
+        default_value_t = 0
+    )]
+    pub change_tolerance: u32,
+
+    /// Directory to write the status of each user to like changes whose event does not exist
+    #[arg(
+        long,
+        global = true,
+        env = "STATUS_DIR",
+        value_hint = ValueHint::DirPath
+    )]
+    pub status_dir: Option<PathBuf>,
+
+    /// Append a JSON line for every change of each build run to this file
+    #[arg(
+        long,
+        global = true,
+        env = "REPORT_FILE",
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath
+    )]
+    pub report_file: Option<PathBuf>,
+
+    /// JSON object mapping old event names to their current name like `{"IE2-IC/01": "IE2-IC-01"}`.
+    /// Only read on start.
+    #[arg(
+        long,
+        global = true,
+        env = "RENAMES_FILE",
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath
+    )]
+    pub renames_file: Option<PathBuf>,
+
+    /// Also serve the calendars via HTTP on this address like `0.0.0.0:8080` while watching
+    #[arg(long, global = true, env = "HTTP_LISTEN", value_name = "ADDRESS")]
+    pub http_listen: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Build all userconfigs once and remove superfluous calendars.
+    ///
+    /// Exits with a failure when a calendar could not be built.
+    BuildAll,
+
+    /// Build a single userconfig once and print the result
+    Build {
+        /// Filename of the userconfig within the userconfig directory like `1337.json`
+        userconfig: String,
+    },
+
+    /// Build all userconfigs and rebuild them on changes. This is the default.
+    Watch,
+
+    /// Check every userconfig and eventfile without building anything.
+    ///
+    /// Prints a line per problem like invalid JSON or an added event without endtime.
+    /// Exits with a failure when there is a problem.
+    Validate,
+}
+
+#[test]
+fn verify() {
+    use clap::CommandFactory as _;
+    Cli::command().debug_assert();
+}
@@ -1,11 +1,13 @@
 use std::fs;
-use std::path::Path;
 
 use anyhow::Context as _;
 use chrono::NaiveDateTime;
+use chrono_tz::Tz;
 use serde::Deserialize;
 
 use crate::generate_ics::{EventStatus, SoonToBeIcsEvent};
+use crate::local_time;
+use crate::settings::Settings;
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
@@ -17,11 +19,13 @@ pub struct EventEntry {
     pub end_time: NaiveDateTime,
 }
 
-pub const FOLDER: &str = "eventfiles";
+/// Eventfiles are named like the event but without slashes
+pub fn source(name: &str) -> String {
+    name.replace('/', "-")
+}
 
-pub fn read(name: &str) -> anyhow::Result<Vec<EventEntry>> {
-    let filename = name.replace('/', "-");
-    let path = Path::new(FOLDER).join(filename + ".json");
+pub fn read(settings: &Settings, name: &str) -> anyhow::Result<Vec<EventEntry>> {
+    let path = settings.eventfiles_dir.join(source(name) + ".json");
     let content = fs::read_to_string(path).context("failed to read")?;
     let event_entries: Vec<EventEntry> =
         serde_json::from_str(&content).context("failed to parse")?;
@@ -29,17 +33,38 @@ pub fn read(name: &str) -> anyhow::Result<Vec<EventEntry>> {
     Ok(event_entries)
 }
 
-impl From<EventEntry> for SoonToBeIcsEvent {
-    fn from(event: EventEntry) -> Self {
-        Self {
-            start_time: event.start_time,
-            end_time: event.end_time,
-            name: event.name.clone(),
-            pretty_name: event.name,
+impl EventEntry {
+    /// The times of the eventfile are local times of the `timezone`.
+    /// When one is ambiguous or does not exist a warning is added.
+    pub fn into_ics_event(
+        self,
+        source: &str,
+        timezone: Tz,
+        warnings: &mut Vec<String>,
+    ) -> SoonToBeIcsEvent {
+        let mut resolve = |local| {
+            let (date_time, warning) = local_time::resolve(timezone, local);
+            if let Some(warning) = warning {
+                warnings.push(format!("event {}: {warning}", self.name));
+            }
+            date_time
+        };
+        let start_time = resolve(self.start_time);
+        let end_time = resolve(self.end_time);
+        SoonToBeIcsEvent {
+            start_time,
+            end_time,
+            name: self.name.clone(),
+            pretty_name: self.name,
+            source: source.to_owned(),
+            original_start_time: start_time,
             status: EventStatus::Confirmed,
-            alert_minutes_before: None,
-            description: event.description,
-            location: event.location,
+            alerts: None,
+            description: self.description,
+            location: self.location,
+            categories: Vec::new(),
+            color: None,
+            transparent: false,
         }
     }
 }
@@ -72,3 +97,20 @@ fn can_deserialize_event_entry() -> Result<(), serde_json::Error> {
 
     Ok(())
 }
+
+#[test]
+fn ambiguous_time_is_warned_about() -> Result<(), serde_json::Error> {
+    let entry: EventEntry = serde_json::from_str(
+        r#"{"Name": "BTI1-TI", "Location": "1060", "Description": "", "StartTime": "2020-10-25T02:30:00", "EndTime": "2020-10-25T04:00:00"}"#,
+    )?;
+    let mut warnings = Vec::new();
+    let event = entry.into_ics_event("BTI1-TI", chrono_tz::Europe::Berlin, &mut warnings);
+
+    assert_eq!(event.start_time.to_rfc3339(), "2020-10-25T02:30:00+02:00");
+    assert_eq!(event.end_time.to_rfc3339(), "2020-10-25T04:00:00+01:00");
+    assert_eq!(
+        warnings,
+        ["event BTI1-TI: 2020-10-25 02:30 occurs twice in Europe/Berlin, the first one is used"]
+    );
+    Ok(())
+}
@@ -1,25 +1,41 @@
-use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fmt::Write as _;
-use std::hash::{Hash, Hasher as _};
 
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use crate::ics_writer::{escape_text, fold_lines};
+use crate::revisions::Revisions;
+use crate::series::{self, Component, Series};
+use crate::stable_hash::fnv1a;
+use crate::userconfig::{AlarmAction, Alert, RecurringEvents, TimeFormat};
+use crate::vtimezone::write_vtimezone;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EventStatus {
     Confirmed,
     Cancelled,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SoonToBeIcsEvent {
     pub name: String,
     pub pretty_name: String,
+    /// Where the event originates from like the eventfile. Part of the UID.
+    pub source: String,
+    /// Start time before changes were applied. Its local time in the zone of the eventfiles is part of the UID.
+    pub original_start_time: DateTime<Tz>,
     pub status: EventStatus,
-    pub start_time: NaiveDateTime,
-    pub end_time: NaiveDateTime,
-    pub alert_minutes_before: Option<u16>,
+    pub start_time: DateTime<Tz>,
+    pub end_time: DateTime<Tz>,
+    /// Set by a change of this occurrence or else by the details of the event
+    pub alerts: Option<Vec<Alert>>,
     pub description: String,
     pub location: String,
+    pub categories: Vec<String>,
+    pub color: Option<String>,
+    /// Free instead of busy time
+    pub transparent: bool,
 }
 
 const ICS_PREFIX: &str = "BEGIN:VCALENDAR
@@ -28,46 +44,183 @@ METHOD:PUBLISH
 PRODID:https://calendarbot.hawhh.de
 ";
 
-const ICS_TIMEZONE: &str = "BEGIN:VTIMEZONE
-TZID:Europe/Berlin
-BEGIN:DAYLIGHT
-TZOFFSETFROM:+0100
-RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU
-DTSTART:19810329T020000
-TZNAME:CEST
-TZOFFSETTO:+0200
-END:DAYLIGHT
-BEGIN:STANDARD
-TZOFFSETFROM:+0200
-RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU
-DTSTART:19961027T030000
-TZNAME:CET
-TZOFFSETTO:+0100
-END:STANDARD
-END:VTIMEZONE
-";
-
 const ICS_SUFFIX: &str = "END:VCALENDAR\n";
 
-pub fn generate_ics(calendarname: &str, events: &[SoonToBeIcsEvent]) -> String {
+/// How the times of the events are written
+#[derive(Clone, Copy)]
+enum Times {
+    Local(Tz),
+    Utc,
+}
+
+impl Times {
+    const fn zone(self) -> Tz {
+        match self {
+            Self::Local(timezone) => timezone,
+            Self::Utc => chrono_tz::UTC,
+        }
+    }
+
+    /// Content line like `DTSTART;TZID=Europe/Berlin:20200409T081500` or `DTSTART:20200409T061500Z`.
+    /// The dates are local times of [`Self::zone`].
+    fn property(self, name: &str, dates: &[NaiveDateTime]) -> String {
+        let dates = dates.iter().map(|date| date_to_ics_date(*date));
+        match self {
+            Self::Local(timezone) => format!(
+                "{name};TZID={}:{}\n",
+                timezone.name(),
+                dates.collect::<Vec<_>>().join(",")
+            ),
+            Self::Utc => format!(
+                "{name}:{}\n",
+                dates.map(|date| date + "Z").collect::<Vec<_>>().join(",")
+            ),
+        }
+    }
+}
+
+/// The `calendarname` is shown by calendar clients like `@HAWHHCalendarBot (Peter)`
+pub fn generate_ics(
+    calendarname: &str,
+    events: &[SoonToBeIcsEvent],
+    recurring_events: RecurringEvents,
+    timezone: Tz,
+    time_format: TimeFormat,
+    revisions: &Revisions,
+    now: DateTime<Utc>,
+) -> String {
     let mut result = String::default();
 
     result += ICS_PREFIX;
-    _ = writeln!(result, "X-WR-CALNAME:@HAWHHCalendarBot ({calendarname})");
-    result += ICS_TIMEZONE;
+    _ = writeln!(result, "X-WR-CALNAME:{}", escape_text(calendarname));
 
-    for event in events {
-        event_as_ics_vevent_string(&mut result, event);
+    let times = match time_format {
+        TimeFormat::Local => {
+            write_vtimezone_of_events(&mut result, events, timezone, now);
+            Times::Local(timezone)
+        }
+        TimeFormat::Utc => Times::Utc,
+    };
+    // Before finding series as weekly rules repeat the local time of the zone they are written in
+    let events = events
+        .iter()
+        .map(|event| in_zone(event, times.zone()))
+        .collect::<Vec<_>>();
+    let events = events.as_slice();
+
+    let components = match recurring_events {
+        RecurringEvents::Separate => events.iter().map(Component::Single).collect(),
+        RecurringEvents::Series => series::find(events),
+    };
+    let mut uids = Uids::default();
+    for component in components {
+        match component {
+            Component::Single(event) => {
+                let uid = uids.unique(&event_identity(event));
+                event_as_ics_vevent_string(&mut result, event, &uid, times, revisions, now);
+            }
+            Component::Series(series) => {
+                let uid = uids.unique(&series.identity);
+                series_as_ics_vevent_strings(&mut result, &series, &uid, times, revisions, now);
+            }
+        }
     }
 
     result += ICS_SUFFIX;
 
-    result.replace('\n', "\r\n")
+    fold_lines(&result)
 }
 
-fn event_as_ics_vevent_string(output: &mut String, event: &SoonToBeIcsEvent) {
+/// VTIMEZONE covering the time range of the events.
+/// A day is added on both sides to be on the safe side with clients.
+fn write_vtimezone_of_events(
+    output: &mut String,
+    events: &[SoonToBeIcsEvent],
+    timezone: Tz,
+    now: DateTime<Utc>,
+) {
+    let start = events
+        .iter()
+        .map(|event| event.start_time.to_utc())
+        .min()
+        .unwrap_or(now);
+    let end = events
+        .iter()
+        .map(|event| event.end_time.to_utc())
+        .max()
+        .unwrap_or(now);
+    write_vtimezone(
+        output,
+        timezone,
+        start - TimeDelta::days(1),
+        end + TimeDelta::days(1),
+    );
+}
+
+/// The original start stays in the zone of the eventfiles as it is part of the UID
+fn in_zone(event: &SoonToBeIcsEvent, timezone: Tz) -> SoonToBeIcsEvent {
+    let mut event = event.clone();
+    event.start_time = event.start_time.with_timezone(&timezone);
+    event.end_time = event.end_time.with_timezone(&timezone);
+    event
+}
+
+fn event_as_ics_vevent_string(
+    output: &mut String,
+    event: &SoonToBeIcsEvent,
+    uid: &str,
+    times: Times,
+    revisions: &Revisions,
+    now: DateTime<Utc>,
+) {
+    let content = event_content_lines(event, uid, times, "");
+    write_vevent(output, &content, revisions, now);
+}
+
+/// The series with its rule followed by the overrides of single occurrences
+fn series_as_ics_vevent_strings(
+    output: &mut String,
+    series: &Series,
+    uid: &str,
+    times: Times,
+    revisions: &Revisions,
+    now: DateTime<Utc>,
+) {
+    let mut rule = format!("RRULE:FREQ=WEEKLY;COUNT={}\n", series.count);
+    if !series.exdates.is_empty() {
+        rule += &times.property("EXDATE", &series.exdates);
+    }
+    let content = event_content_lines(&series.first, uid, times, &rule);
+    write_vevent(output, &content, revisions, now);
+
+    for (recurrence_id, event) in &series.overrides {
+        let recurrence_id = times.property("RECURRENCE-ID", &[*recurrence_id]);
+        let content = event_content_lines(event, uid, times, &recurrence_id);
+        write_vevent(output, &content, revisions, now);
+    }
+}
+
+fn write_vevent(output: &mut String, content: &str, revisions: &Revisions, now: DateTime<Utc>) {
     *output += "BEGIN:VEVENT\n";
-    *output += "TRANSP:OPAQUE\n";
+    *output += content;
+    revisions.next(content, now).write_content_lines(output);
+    *output += "END:VEVENT\n";
+}
+
+/// Every content line of the VEVENT besides the ones describing its revision.
+/// `recurrence` lines are placed right after the time of the event.
+fn event_content_lines(
+    event: &SoonToBeIcsEvent,
+    uid: &str,
+    times: Times,
+    recurrence: &str,
+) -> String {
+    let mut output = String::new();
+    output += if event.transparent {
+        "TRANSP:TRANSPARENT\n"
+    } else {
+        "TRANSP:OPAQUE\n"
+    };
 
     _ = writeln!(
         output,
@@ -79,81 +232,99 @@ fn event_as_ics_vevent_string(output: &mut String, event: &SoonToBeIcsEvent) {
         .to_owned()
     );
 
-    _ = writeln!(
-        output,
-        "SUMMARY:{}",
-        string_to_ical_escaped_text(&event.pretty_name)
-    );
-    _ = writeln!(
-        output,
-        "DTSTART;TZID=Europe/Berlin:{}",
-        date_to_ics_date(event.start_time)
-    );
-    _ = writeln!(
-        output,
-        "DTEND;TZID=Europe/Berlin:{}",
-        date_to_ics_date(event.end_time)
-    );
+    _ = writeln!(output, "SUMMARY:{}", escape_text(&event.pretty_name));
+    output += &times.property("DTSTART", &[event.start_time.naive_local()]);
+    output += &times.property("DTEND", &[event.end_time.naive_local()]);
+    output += recurrence;
 
     if !event.location.is_empty() {
-        _ = writeln!(
-            output,
-            "LOCATION:{}",
-            string_to_ical_escaped_text(&event.location)
-        );
+        _ = writeln!(output, "LOCATION:{}", escape_text(&event.location));
     }
 
     if !event.description.is_empty() {
-        _ = writeln!(
-            output,
-            "DESCRIPTION:{}",
-            string_to_ical_escaped_text(&event.description)
-        );
+        _ = writeln!(output, "DESCRIPTION:{}", escape_text(&event.description));
     }
 
-    *output += "URL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\n";
-    _ = writeln!(
-        output,
-        "UID:{}@calendarbot.hawhh.de",
-        calculate_event_hash(event)
-    );
+    if !event.categories.is_empty() {
+        let categories = event
+            .categories
+            .iter()
+            .map(|category| escape_text(category))
+            .collect::<Vec<_>>()
+            .join(",");
+        _ = writeln!(output, "CATEGORIES:{categories}");
+    }
 
-    if let Some(minutes_before) = event.alert_minutes_before {
-        create_valarm(output, minutes_before);
+    if let Some(color) = &event.color {
+        _ = writeln!(output, "COLOR:{}", escape_text(color));
     }
 
-    *output += "END:VEVENT\n";
+    output += "URL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\n";
+    _ = writeln!(output, "UID:{uid}");
+
+    for alert in event.alerts.iter().flatten() {
+        create_valarm(&mut output, alert, &event.pretty_name);
+    }
+
+    output
 }
 
-/// escape according to <https://www.kanzaki.com/docs/ical/text.html>
-fn string_to_ical_escaped_text(text: &str) -> String {
-    text.replace('\\', "\\\\")
-        .replace(',', "\\,")
-        .replace(';', "\\;")
-        .replace('\n', "\\n")
+/// Only based on the identity of the event so changes like another room keep the UID.
+fn event_identity(event: &SoonToBeIcsEvent) -> String {
+    format!(
+        "{}\n{}\n{}",
+        event.source,
+        event.name,
+        date_to_ics_date(event.original_start_time.naive_local())
+    )
 }
 
-fn calculate_event_hash(event: &SoonToBeIcsEvent) -> String {
-    format!("{:x}", calculate_hash(&event))
+/// UIDs of the components of a calendar.
+/// An identity occurring multiple times like a duplicated eventfile entry gets its index
+/// among the identical ones appended so every UID stays unique and keeps its value on rebuilds.
+#[derive(Default)]
+struct Uids(HashMap<String, usize>);
+
+impl Uids {
+    fn unique(&mut self, identity: &str) -> String {
+        let index = self.0.entry(identity.to_owned()).or_default();
+        let uid = if *index == 0 {
+            uid_of_identity(identity)
+        } else {
+            uid_of_identity(&format!("{identity}\n{index}"))
+        };
+        *index += 1;
+        uid
+    }
 }
 
-fn calculate_hash<T: Hash>(hashable: &T) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    hashable.hash(&mut hasher);
-    hasher.finish()
+fn uid_of_identity(identity: &str) -> String {
+    format!("{:016x}@calendarbot.hawhh.de", fnv1a(identity.as_bytes()))
 }
 
 fn date_to_ics_date(date: NaiveDateTime) -> String {
     date.format("%Y%m%d %H%M%S").to_string().replace(' ', "T")
 }
 
-/// <https://www.kanzaki.com/docs/ical/valarm.html>
-fn create_valarm(output: &mut String, minutes_before: u16) {
+/// <https://www.rfc-editor.org/rfc/rfc5545#section-3.6.6>
+fn create_valarm(output: &mut String, alert: &Alert, event_name: &str) {
     _ = writeln!(
         output,
-        "BEGIN:VALARM\nTRIGGER:-PT{}\nACTION:AUDIO\nEND:VALARM",
-        minutes_to_ical_duration(minutes_before)
+        "BEGIN:VALARM\nTRIGGER:-PT{}",
+        minutes_to_ical_duration(alert.minutes_before)
     );
+    match alert.action {
+        AlarmAction::Audio => *output += "ACTION:AUDIO\n",
+        AlarmAction::Display => {
+            let description = alert.description.as_deref().unwrap_or(event_name);
+            _ = writeln!(
+                output,
+                "ACTION:DISPLAY\nDESCRIPTION:{}",
+                escape_text(description)
+            );
+        }
+    }
+    *output += "END:VALARM\n";
 }
 
 /// <https://www.kanzaki.com/docs/ical/duration-t.html>
@@ -179,43 +350,148 @@ fn parse_ics_date() {
     assert_eq!(result, "20200822T083000");
 }
 
-#[test]
-fn create_minimal_event_vevent() {
-    let event = SoonToBeIcsEvent {
+#[cfg(test)]
+fn create_minimal_event() -> SoonToBeIcsEvent {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+        .unwrap()
+        .and_hms_opt(8, 30, 0)
+        .unwrap()
+        .and_local_timezone(chrono_tz::Europe::Berlin)
+        .unwrap();
+    SoonToBeIcsEvent {
         name: "BTI5-VS".to_owned(),
         pretty_name: "BTI5-VS".to_owned(),
+        source: "BTI5-VS".to_owned(),
+        original_start_time: start_time,
         status: EventStatus::Cancelled,
-        start_time: chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
-            .unwrap()
-            .and_hms_opt(8, 30, 0)
-            .unwrap(),
-        end_time: chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
-            .unwrap()
-            .and_hms_opt(11, 30, 0)
-            .unwrap(),
-        alert_minutes_before: None,
+        start_time,
+        end_time: start_time + TimeDelta::hours(3),
+        alerts: None,
         description: String::new(),
         location: String::new(),
-    };
+        categories: Vec::new(),
+        color: None,
+        transparent: false,
+    }
+}
+
+#[cfg(test)]
+const fn now() -> DateTime<Utc> {
+    chrono::NaiveDate::from_ymd_opt(2020, 8, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc()
+}
+
+#[test]
+fn create_minimal_event_vevent() {
+    let event = create_minimal_event();
 
     let mut result = String::new();
-    event_as_ics_vevent_string(&mut result, &event);
+    event_as_ics_vevent_string(
+        &mut result,
+        &event,
+        &uid_of_identity(&event_identity(&event)),
+        Times::Local(chrono_tz::Europe::Berlin),
+        &Revisions::default(),
+        now(),
+    );
     assert_eq!(
         result,
-        "BEGIN:VEVENT\nTRANSP:OPAQUE\nSTATUS:CANCELLED\nSUMMARY:BTI5-VS\nDTSTART;TZID=Europe/Berlin:20200822T083000\nDTEND;TZID=Europe/Berlin:20200822T113000\nURL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\nUID:dbbd48a01ce77b8c@calendarbot.hawhh.de\nEND:VEVENT\n"
+        "BEGIN:VEVENT\nTRANSP:OPAQUE\nSTATUS:CANCELLED\nSUMMARY:BTI5-VS\nDTSTART;TZID=Europe/Berlin:20200822T083000\nDTEND;TZID=Europe/Berlin:20200822T113000\nURL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\nUID:b81ef0be0989db24@calendarbot.hawhh.de\nSEQUENCE:0\nLAST-MODIFIED:20200801T120000Z\nEND:VEVENT\n"
+    );
+}
+
+#[test]
+fn uid_stays_the_same_on_changes() {
+    let original = create_minimal_event();
+    let mut changed = create_minimal_event();
+    changed.pretty_name = "BTI5-VS whatever".to_owned();
+    changed.status = EventStatus::Confirmed;
+    changed.location = "1060".to_owned();
+    changed.description = "some notes".to_owned();
+    changed.start_time += TimeDelta::minutes(15);
+    assert_eq!(event_identity(&original), event_identity(&changed));
+
+    changed.original_start_time = changed.start_time;
+    assert_ne!(event_identity(&original), event_identity(&changed));
+}
+
+#[test]
+fn changed_event_gets_next_sequence() {
+    let event = create_minimal_event();
+    let previous = generate_ics(
+        "Peter",
+        &[event],
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::default(),
+        now(),
+    );
+
+    let mut event = create_minimal_event();
+    event.location = "1060".to_owned();
+    let later = now() + TimeDelta::days(1);
+    let result = generate_ics(
+        "Peter",
+        &[event],
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::from_ics(&previous),
+        later,
+    );
+    assert!(result.contains("\r\nSEQUENCE:1\r\nLAST-MODIFIED:20200802T120000Z\r\n"));
+
+    let again = generate_ics(
+        "Peter",
+        &[create_minimal_event()],
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::from_ics(&previous),
+        later,
     );
+    assert_eq!(again, previous);
 }
 
 #[test]
 fn create_valarm_example() {
     let mut output = String::new();
-    create_valarm(&mut output, 10);
+    let alert = Alert {
+        minutes_before: 10,
+        action: AlarmAction::Audio,
+        description: None,
+    };
+    create_valarm(&mut output, &alert, "BTI5-VS");
     assert_eq!(
         output,
         "BEGIN:VALARM\nTRIGGER:-PT10M\nACTION:AUDIO\nEND:VALARM\n"
     );
 }
 
+#[test]
+fn create_display_valarm() {
+    let mut output = String::new();
+    let mut alert = Alert {
+        minutes_before: 1440,
+        action: AlarmAction::Display,
+        description: None,
+    };
+    create_valarm(&mut output, &alert, "BTI5-VS, Klausur");
+    assert_eq!(
+        output,
+        "BEGIN:VALARM\nTRIGGER:-PT24H\nACTION:DISPLAY\nDESCRIPTION:BTI5-VS\\, Klausur\nEND:VALARM\n"
+    );
+
+    output.clear();
+    alert.description = Some("Lernen!".to_owned());
+    create_valarm(&mut output, &alert, "BTI5-VS");
+    assert!(output.contains("\nDESCRIPTION:Lernen!\n"));
+}
+
 #[test]
 fn minutes_to_ical_duration_examples() {
     assert_eq!(minutes_to_ical_duration(0), "00M");
@@ -225,3 +501,180 @@ fn minutes_to_ical_duration_examples() {
     assert_eq!(minutes_to_ical_duration(90), "01H30M");
     assert_eq!(minutes_to_ical_duration(120), "02H");
 }
+
+#[test]
+fn long_description_is_folded() {
+    let mut event = create_minimal_event();
+    event.description = format!("Dozent: HTM\r\n\r\n{}", "Notiz ".repeat(30));
+    let result = generate_ics(
+        "Peter",
+        &[event],
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::default(),
+        now(),
+    );
+
+    assert!(result.contains("\r\nDESCRIPTION:Dozent: HTM\\n\\nNotiz Notiz "));
+    for line in result.split_terminator("\r\n") {
+        assert!(line.len() <= 75, "line too long: {line}");
+    }
+}
+
+#[test]
+fn weekly_events_are_written_as_series() {
+    let week = TimeDelta::weeks(1);
+    let first = create_minimal_event();
+    let mut moved = create_minimal_event();
+    moved.original_start_time += week * 2;
+    moved.start_time += week * 2 + TimeDelta::minutes(15);
+    moved.end_time += week * 2;
+    let mut last = create_minimal_event();
+    last.original_start_time += week * 3;
+    last.start_time += week * 3;
+    last.end_time += week * 3;
+
+    let result = generate_ics(
+        "Peter",
+        &[first, moved, last],
+        RecurringEvents::Series,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::default(),
+        now(),
+    );
+    let uid = uid_of_identity("BTI5-VS\nBTI5-VS\nweekly Sat 08:30:00");
+    assert!(result.ends_with(&format!("END:VTIMEZONE\r\nBEGIN:VEVENT\r\nTRANSP:OPAQUE\r\nSTATUS:CANCELLED\r\nSUMMARY:BTI5-VS\r\nDTSTART;TZID=Europe/Berlin:20200822T083000\r\nDTEND;TZID=Europe/Berlin:20200822T113000\r\nRRULE:FREQ=WEEKLY;COUNT=4\r\nEXDATE;TZID=Europe/Berlin:20200829T083000\r\nURL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\r\nUID:{uid}\r\nSEQUENCE:0\r\nLAST-MODIFIED:20200801T120000Z\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nTRANSP:OPAQUE\r\nSTATUS:CANCELLED\r\nSUMMARY:BTI5-VS\r\nDTSTART;TZID=Europe/Berlin:20200905T084500\r\nDTEND;TZID=Europe/Berlin:20200905T113000\r\nRECURRENCE-ID;TZID=Europe/Berlin:20200905T083000\r\nURL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\r\nUID:{uid}\r\nSEQUENCE:0\r\nLAST-MODIFIED:20200801T120000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n")));
+}
+
+#[test]
+fn vtimezone_covers_only_the_events() {
+    let result = generate_ics(
+        "Peter",
+        &[create_minimal_event()],
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::default(),
+        now(),
+    );
+    assert!(result.contains("\r\nBEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nBEGIN:DAYLIGHT\r\nDTSTART:20200821T083000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0200\r\nTZNAME:CEST\r\nEND:DAYLIGHT\r\nEND:VTIMEZONE\r\n"));
+}
+
+#[test]
+fn utc_times_without_vtimezone() {
+    let result = generate_ics(
+        "Peter",
+        &[create_minimal_event()],
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Utc,
+        &Revisions::default(),
+        now(),
+    );
+    assert!(!result.contains("VTIMEZONE"));
+    assert!(!result.contains("TZID"));
+    assert!(result.contains("\r\nDTSTART:20200822T063000Z\r\nDTEND:20200822T093000Z\r\n"));
+
+    let local = generate_ics(
+        "Peter",
+        &[create_minimal_event()],
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::default(),
+        now(),
+    );
+    let uid = |ics: &str| {
+        ics.lines()
+            .find(|line| line.starts_with("UID:"))
+            .map(ToOwned::to_owned)
+    };
+    assert_eq!(uid(&result), uid(&local));
+}
+
+#[cfg(test)]
+fn uids_of(events: &[SoonToBeIcsEvent]) -> Vec<String> {
+    generate_ics(
+        "Peter",
+        events,
+        RecurringEvents::Separate,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Local,
+        &Revisions::default(),
+        now(),
+    )
+    .lines()
+    .filter(|line| line.starts_with("UID:"))
+    .map(ToOwned::to_owned)
+    .collect()
+}
+
+#[test]
+fn identical_events_get_unique_uids() {
+    let mut other = create_minimal_event();
+    other.name = "BTI5-VSP".to_owned();
+    let events = [create_minimal_event(), other, create_minimal_event()];
+    let result = uids_of(&events);
+    assert_eq!(result.len(), 3);
+    assert_ne!(result[0], result[1]);
+    assert_ne!(result[0], result[2]);
+    assert_ne!(result[1], result[2]);
+    assert_eq!(result, uids_of(&events));
+    assert_eq!(result[0], uids_of(&events[..1])[0]);
+}
+
+#[test]
+fn additions_on_the_same_day_keep_their_uid_when_edited() {
+    let mut first = create_minimal_event();
+    first.source = "userconfig".to_owned();
+    let mut second = first.clone();
+    second.end_time += TimeDelta::hours(1);
+    let result = uids_of(&[first.clone(), second.clone()]);
+    assert_ne!(result[0], result[1]);
+
+    first.end_time += TimeDelta::minutes(30);
+    first.pretty_name = "Nachklausur".to_owned();
+    assert_eq!(result, uids_of(&[first, second]));
+}
+
+#[test]
+fn utc_series_across_dst_change() {
+    let mut events = Vec::new();
+    for week in 8..11 {
+        let mut event = create_minimal_event();
+        let delta = TimeDelta::weeks(week);
+        event.original_start_time = crate::local_time::shift(event.original_start_time, delta);
+        event.start_time = crate::local_time::shift(event.start_time, delta);
+        event.end_time = crate::local_time::shift(event.end_time, delta);
+        events.push(event);
+    }
+
+    let result = generate_ics(
+        "Peter",
+        &events,
+        RecurringEvents::Series,
+        chrono_tz::Europe::Berlin,
+        TimeFormat::Utc,
+        &Revisions::default(),
+        now(),
+    );
+    // The rule keeps the UTC time so the occurrence after the change to winter time is an override
+    assert!(result.contains(
+        "\r\nDTSTART:20201017T063000Z\r\nDTEND:20201017T093000Z\r\nRRULE:FREQ=WEEKLY;COUNT=3\r\n"
+    ));
+    assert!(result.contains("\r\nDTSTART:20201031T073000Z\r\nDTEND:20201031T103000Z\r\nRECURRENCE-ID:20201031T063000Z\r\n"));
+}
+
+#[test]
+fn categories_color_and_transparency_are_written() {
+    let mut event = create_minimal_event();
+    event.categories = vec!["tutorial".to_owned(), "optional, maybe".to_owned()];
+    event.color = Some("turquoise".to_owned());
+    event.transparent = true;
+
+    let content = event_content_lines(&event, "uid", Times::Local(chrono_tz::Europe::Berlin), "");
+    assert!(content.starts_with("TRANSP:TRANSPARENT\n"));
+    assert!(content.contains("\nCATEGORIES:tutorial,optional\\, maybe\nCOLOR:turquoise\n"));
+}
@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write as _};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::stable_hash::fnv1a;
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+const MAX_HEADER_LINES: usize = 100;
+/// Limits the memory a single request can use as lines are read until their end
+const MAX_REQUEST_BYTES: u64 = 16 * 1024;
+/// Whole time a client has to send its request so slow clients can not hold a connection forever
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Connections beyond this are closed right away instead of spawning even more threads
+const MAX_CONNECTIONS: usize = 64;
+/// Compared against for unknown chat ids so they take as long as a wrong suffix
+const DUMMY_SUFFIX: &str = "0000000000000000";
+
+#[derive(Clone)]
+struct Calendar {
+    suffix: String,
+    content: Arc<str>,
+    etag: String,
+    last_modified: DateTime<Utc>,
+}
+
+/// Calendars as served by the built-in HTTP server.
+///
+/// Filled with the existing calendar files on start and by the build afterwards so the server never has to touch the file system.
+#[derive(Clone, Default)]
+pub struct Calendars(Arc<RwLock<HashMap<i64, Calendar>>>);
+
+impl Calendars {
+    pub fn publish(&self, chat_id: i64, suffix: &str, content: &str, last_modified: DateTime<Utc>) {
+        let calendar = Calendar {
+            suffix: suffix.to_owned(),
+            content: content.into(),
+            etag: format!("\"{:016x}\"", fnv1a(content.as_bytes())),
+            last_modified,
+        };
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(chat_id, calendar);
+    }
+
+    pub fn remove(&self, chat_id: i64) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&chat_id);
+    }
+
+    pub fn retain(&self, chat_ids: &[i64]) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|chat_id, _| chat_ids.contains(chat_id));
+    }
+
+    fn lookup(&self, chat_id: i64, suffix: &str) -> Option<Calendar> {
+        let calendar = self
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&chat_id)
+            .cloned();
+        // Always compare something so a missing chat id takes the same path as a wrong suffix
+        let expected_suffix = calendar
+            .as_ref()
+            .map_or(DUMMY_SUFFIX, |calendar| calendar.suffix.as_str());
+        let suffix_matches = constant_time_eq(expected_suffix.as_bytes(), suffix.as_bytes());
+        calendar.filter(|_| suffix_matches)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Option<Arc<str>>,
+}
+
+impl Response {
+    const fn empty(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    fn not_found() -> Self {
+        Self {
+            status: "404 Not Found",
+            headers: vec![("Content-Type", "text/plain; charset=utf-8".to_owned())],
+            body: Some("Not Found\n".into()),
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+pub fn serve(address: &str, calendars: Calendars) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!("HTTP server listens on {}", listener.local_addr()?);
+
+    let open_connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let Some(slot) = ConnectionSlot::take(&open_connections) else {
+                        println!("HTTP connection dropped: too many open connections");
+                        continue;
+                    };
+                    let calendars = calendars.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = handle_connection(&stream, &calendars) {
+                            println!("HTTP connection failed: {err}");
+                        }
+                        drop(slot);
+                    });
+                }
+                Err(err) => println!("HTTP accept failed: {err}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// One of the [`MAX_CONNECTIONS`] which is given back when dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn take(open_connections: &Arc<AtomicUsize>) -> Option<Self> {
+        open_connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| {
+                (open < MAX_CONNECTIONS).then_some(open + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(open_connections)))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+fn handle_connection(stream: &TcpStream, calendars: &Calendars) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(Duration::from_secs(30)))?;
+
+    let mut reader = BufReader::new(DeadlineReader {
+        stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+    let (response, head_only) = match read_request(&mut reader)? {
+        Some(request) => (respond(&request, calendars), request.method == "HEAD"),
+        None => (Response::empty("400 Bad Request"), false),
+    };
+
+    let mut writer = stream;
+    write_response(&mut writer, &response, head_only)?;
+    writer.flush()
+}
+
+/// Reads of the stream fail once the `deadline` passed, no matter how many bytes arrived until then
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+fn write_response<W: std::io::Write>(
+    writer: &mut W,
+    response: &Response,
+    head_only: bool,
+) -> std::io::Result<()> {
+    write!(writer, "HTTP/1.1 {}\r\n", response.status)?;
+    for (name, value) in &response.headers {
+        write!(writer, "{name}: {value}\r\n")?;
+    }
+    let body = response.body.as_deref().unwrap_or_default();
+    if response.body.is_some() {
+        write!(writer, "Content-Length: {}\r\n", body.len())?;
+    }
+    write!(writer, "Connection: close\r\n\r\n")?;
+    if !head_only {
+        writer.write_all(body.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> std::io::Result<Option<Request>> {
+    let mut reader = reader.take(MAX_REQUEST_BYTES);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(None);
+    };
+    let mut request = Request {
+        method: method.to_owned(),
+        path: target.split('?').next().unwrap_or_default().to_owned(),
+        if_none_match: None,
+        if_modified_since: None,
+    };
+
+    for _ in 0..MAX_HEADER_LINES {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            return Ok(Some(request));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = Some(value.trim().to_owned());
+            if name.eq_ignore_ascii_case("If-None-Match") {
+                request.if_none_match = value;
+            } else if name.eq_ignore_ascii_case("If-Modified-Since") {
+                request.if_modified_since = value;
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn respond(request: &Request, calendars: &Calendars) -> Response {
+    if !matches!(request.method.as_str(), "GET" | "HEAD") {
+        let mut response = Response::empty("405 Method Not Allowed");
+        response.headers.push(("Allow", "GET, HEAD".to_owned()));
+        return response;
+    }
+
+    let Some((chat_id, suffix)) = parse_path(&request.path) else {
+        return Response::not_found();
+    };
+
+    let Some(calendar) = calendars.lookup(chat_id, suffix) else {
+        return Response::not_found();
+    };
+
+    let not_modified = is_not_modified(request, &calendar);
+    let headers = vec![
+        ("Content-Type", "text/calendar; charset=utf-8".to_owned()),
+        ("ETag", calendar.etag),
+        (
+            "Last-Modified",
+            calendar.last_modified.format(HTTP_DATE_FORMAT).to_string(),
+        ),
+    ];
+
+    if not_modified {
+        return Response {
+            status: "304 Not Modified",
+            headers,
+            body: None,
+        };
+    }
+
+    Response {
+        status: "200 OK",
+        headers,
+        body: Some(calendar.content),
+    }
+}
+
+/// <https://www.rfc-editor.org/rfc/rfc9110#section-13.2.2>
+fn is_not_modified(request: &Request, calendar: &Calendar) -> bool {
+    if let Some(if_none_match) = &request.if_none_match {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|etag| etag == "*" || etag.strip_prefix("W/").unwrap_or(etag) == calendar.etag);
+    }
+
+    request
+        .if_modified_since
+        .as_deref()
+        .and_then(|since| NaiveDateTime::parse_from_str(since, HTTP_DATE_FORMAT).ok())
+        .is_some_and(|since| calendar.last_modified.timestamp() <= since.and_utc().timestamp())
+}
+
+/// `<chatid>-<suffix>.ics` where group chats have a negative id like `-42-<suffix>.ics`
+pub fn parse_calendar_filename(filename: &str) -> Option<(i64, &str)> {
+    let name = filename.strip_suffix(".ics")?;
+    let (sign, unsigned) = name.split_at(usize::from(name.starts_with('-')));
+    let (digits, suffix) = unsigned.split_once('-')?;
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((format!("{sign}{digits}").parse().ok()?, suffix))
+}
+
+fn parse_path(path: &str) -> Option<(i64, &str)> {
+    parse_calendar_filename(path.strip_prefix('/')?)
+}
+
+/// Compares without returning early so the time taken doesn't tell how much of a secret was guessed correctly.
+/// The time only depends on the length of `actual` so it doesn't tell the length of `expected` either.
+fn constant_time_eq(expected: &[u8], actual: &[u8]) -> bool {
+    let mut difference = u8::from(expected.len() != actual.len());
+    for (index, byte) in actual.iter().enumerate() {
+        difference |= byte ^ expected.get(index).copied().unwrap_or_default();
+    }
+    difference == 0
+}
+
+#[cfg(test)]
+fn create_calendars() -> Calendars {
+    let calendars = Calendars::default();
+    calendars.publish(
+        42,
+        "secret",
+        "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n",
+        chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap()
+            .and_utc(),
+    );
+    calendars
+}
+
+#[cfg(test)]
+fn get(path: &str) -> Request {
+    Request {
+        method: "GET".to_owned(),
+        path: path.to_owned(),
+        if_none_match: None,
+        if_modified_since: None,
+    }
+}
+
+#[test]
+fn parse_path_examples() {
+    assert_eq!(parse_path("/42-secret.ics"), Some((42, "secret")));
+    assert_eq!(parse_path("/-42-secret.ics"), Some((-42, "secret")));
+    assert_eq!(parse_path("/--42-secret.ics"), None);
+    assert_eq!(parse_path("/+42-secret.ics"), None);
+    assert_eq!(parse_path("/42-secret"), None);
+    assert_eq!(parse_path("/42.ics"), None);
+    assert_eq!(parse_path("/peter-secret.ics"), None);
+    assert_eq!(parse_path("42-secret.ics"), None);
+}
+
+#[test]
+fn parse_calendar_filename_examples() {
+    assert_eq!(
+        parse_calendar_filename("42-secret.ics"),
+        Some((42, "secret"))
+    );
+    assert_eq!(
+        parse_calendar_filename("-42-secret.ics"),
+        Some((-42, "secret"))
+    );
+    assert_eq!(parse_calendar_filename(".42-secret.ics.tmp"), None);
+    assert_eq!(parse_calendar_filename("42-secret.json"), None);
+}
+
+#[test]
+fn constant_time_eq_examples() {
+    assert!(constant_time_eq(b"secret", b"secret"));
+    assert!(!constant_time_eq(b"secret", b"secreT"));
+    assert!(!constant_time_eq(b"secret", b"secret2"));
+    assert!(!constant_time_eq(b"secret", b"secre"));
+    assert!(!constant_time_eq(b"", b"secret"));
+    assert!(!constant_time_eq(b"secret", b""));
+    assert!(constant_time_eq(b"", b""));
+}
+
+#[test]
+fn can_read_request() {
+    let raw =
+        "GET /42-secret.ics?foo=bar HTTP/1.1\r\nHost: localhost\r\nif-none-match: \"abc\"\r\n\r\n";
+    let request = read_request(&mut raw.as_bytes()).unwrap().unwrap();
+    assert_eq!(request.method, "GET");
+    assert_eq!(request.path, "/42-secret.ics");
+    assert_eq!(request.if_none_match.as_deref(), Some("\"abc\""));
+    assert_eq!(request.if_modified_since, None);
+}
+
+#[test]
+fn incomplete_request_is_rejected() {
+    let raw = "GET /42-secret.ics HTTP/1.1\r\nHost: localhost\r\n";
+    assert!(read_request(&mut raw.as_bytes()).unwrap().is_none());
+}
+
+#[test]
+fn endless_line_is_rejected() {
+    let raw = "GET /".to_owned() + &"a".repeat(100_000);
+    assert!(read_request(&mut raw.as_bytes()).unwrap().is_none());
+}
+
+#[test]
+fn slow_request_fails_at_deadline() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    client
+        .write_all(b"GET /42-secret.ics HTTP/1.1\r\n")
+        .unwrap();
+
+    let start = Instant::now();
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: &stream,
+        deadline: start + Duration::from_millis(200),
+    });
+    assert!(read_request(&mut reader).is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let mut reader = DeadlineReader {
+        stream: &stream,
+        deadline: start,
+    };
+    let error = reader.read(&mut [0; 8]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+}
+
+#[test]
+fn serves_calendar_of_group_chat() {
+    let calendars = create_calendars();
+    calendars.publish(
+        -42,
+        "group",
+        "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n",
+        DateTime::UNIX_EPOCH,
+    );
+    assert_eq!(respond(&get("/-42-group.ics"), &calendars).status, "200 OK");
+}
+
+#[test]
+fn serves_calendar() {
+    let response = respond(&get("/42-secret.ics"), &create_calendars());
+    assert_eq!(response.status, "200 OK");
+    assert_eq!(
+        response.headers,
+        [
+            ("Content-Type", "text/calendar; charset=utf-8".to_owned()),
+            ("ETag", "\"265c87adaf13c32d\"".to_owned()),
+            ("Last-Modified", "Sat, 22 Aug 2020 08:30:00 GMT".to_owned()),
+        ]
+    );
+    assert_eq!(
+        response.body.as_deref(),
+        Some("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n")
+    );
+}
+
+#[test]
+fn unknown_chat_and_wrong_suffix_look_the_same() {
+    let calendars = create_calendars();
+    let unknown_chat = respond(&get("/666-secret.ics"), &calendars);
+    let wrong_suffix = respond(&get("/42-guessed.ics"), &calendars);
+    assert_eq!(unknown_chat, Response::not_found());
+    assert_eq!(wrong_suffix, Response::not_found());
+}
+
+#[test]
+fn matching_etag_is_not_modified() {
+    let mut request = get("/42-secret.ics");
+    request.if_none_match = Some("\"other\", W/\"265c87adaf13c32d\"".to_owned());
+    let response = respond(&request, &create_calendars());
+    assert_eq!(response.status, "304 Not Modified");
+    assert_eq!(response.body, None);
+}
+
+#[test]
+fn other_etag_is_served() {
+    let mut request = get("/42-secret.ics");
+    request.if_none_match = Some("\"other\"".to_owned());
+    request.if_modified_since = Some("Sat, 22 Aug 2020 08:30:00 GMT".to_owned());
+    let response = respond(&request, &create_calendars());
+    assert_eq!(response.status, "200 OK");
+}
+
+#[test]
+fn if_modified_since_is_respected() {
+    let calendars = create_calendars();
+    let mut request = get("/42-secret.ics");
+    request.if_modified_since = Some("Sat, 22 Aug 2020 08:30:00 GMT".to_owned());
+    assert_eq!(respond(&request, &calendars).status, "304 Not Modified");
+
+    request.if_modified_since = Some("Sat, 22 Aug 2020 08:29:59 GMT".to_owned());
+    assert_eq!(respond(&request, &calendars).status, "200 OK");
+}
+
+#[test]
+fn can_write_response() {
+    let response = respond(&get("/42-secret.ics"), &create_calendars());
+    let mut output = Vec::new();
+    write_response(&mut output, &response, false).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Type: text/calendar; charset=utf-8\r\nETag: \"265c87adaf13c32d\"\r\nLast-Modified: Sat, 22 Aug 2020 08:30:00 GMT\r\nContent-Length: 32\r\nConnection: close\r\n\r\nBEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n"
+    );
+}
+
+#[test]
+fn head_has_no_body() {
+    let response = respond(&get("/42-secret.ics"), &create_calendars());
+    let mut output = Vec::new();
+    write_response(&mut output, &response, true).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("\r\nContent-Length: 32\r\n"));
+    assert!(output.ends_with("\r\n\r\n"));
+}
+
+#[test]
+fn post_is_not_allowed() {
+    let mut request = get("/42-secret.ics");
+    request.method = "POST".to_owned();
+    let response = respond(&request, &create_calendars());
+    assert_eq!(response.status, "405 Method Not Allowed");
+}
@@ -0,0 +1,127 @@
+/// Maximum length of a content line in octets excluding the line break
+/// <https://www.rfc-editor.org/rfc/rfc5545#section-3.1>
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Escape according to <https://www.rfc-editor.org/rfc/rfc5545#section-3.3.11>
+///
+/// Every kind of line break ends up as `\n`.
+/// Other control characters are not allowed in TEXT and are dropped.
+pub fn escape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => result += "\\\\",
+            ',' => result += "\\,",
+            ';' => result += "\\;",
+            '\n' => result += "\\n",
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                result += "\\n";
+            }
+            '\t' => result.push(char),
+            char if char.is_control() => {}
+            char => result.push(char),
+        }
+    }
+    result
+}
+
+/// Turns `\n` separated content lines into CRLF separated content lines which are folded after 75 octets.
+/// Lines are only split between UTF-8 characters.
+pub fn fold_lines(unfolded: &str) -> String {
+    let mut result = String::with_capacity(unfolded.len() + unfolded.len() / 32);
+    for line in unfolded.lines() {
+        fold_line(&mut result, line);
+    }
+    result
+}
+
+fn fold_line(output: &mut String, line: &str) {
+    let mut line_octets = 0;
+    for char in line.chars() {
+        if line_octets + char.len_utf8() > MAX_LINE_OCTETS {
+            *output += "\r\n ";
+            line_octets = 1;
+        }
+        output.push(char);
+        line_octets += char.len_utf8();
+    }
+    *output += "\r\n";
+}
+
+/// Content lines with the folding removed
+pub fn unfold_lines(folded: &str) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for line in folded.lines() {
+        if let Some(continuation) = line.strip_prefix([' ', '\t'])
+            && let Some(last) = result.last_mut()
+        {
+            *last += continuation;
+        } else {
+            result.push(line.to_owned());
+        }
+    }
+    result
+}
+
+#[test]
+fn escape_text_examples() {
+    assert_eq!(escape_text("BTI5-VS"), "BTI5-VS");
+    assert_eq!(escape_text("a,b;c\\d"), "a\\,b\\;c\\\\d");
+    assert_eq!(escape_text("a\nb"), "a\\nb");
+    assert_eq!(escape_text("a\r\nb"), "a\\nb");
+    assert_eq!(escape_text("a\rb"), "a\\nb");
+    assert_eq!(escape_text("a\r\n\r\nb"), "a\\n\\nb");
+    assert_eq!(escape_text("a\tb"), "a\tb");
+    assert_eq!(escape_text("a\u{7}b\u{0}"), "ab");
+    assert_eq!(escape_text("Hörsaal 🚫"), "Hörsaal 🚫");
+}
+
+#[test]
+fn short_lines_are_not_folded() {
+    assert_eq!(
+        fold_lines("BEGIN:VEVENT\nEND:VEVENT\n"),
+        "BEGIN:VEVENT\r\nEND:VEVENT\r\n"
+    );
+    let line = format!("SUMMARY:{}", "a".repeat(67));
+    assert_eq!(line.len(), 75);
+    assert_eq!(fold_lines(&line), format!("{line}\r\n"));
+}
+
+#[test]
+fn long_line_is_folded_after_75_octets() {
+    let line = format!("SUMMARY:{}", "a".repeat(68));
+    assert_eq!(
+        fold_lines(&line),
+        format!("SUMMARY:{}\r\n a\r\n", "a".repeat(67))
+    );
+}
+
+#[test]
+fn folded_lines_are_never_longer_than_75_octets() {
+    let description = format!("DESCRIPTION:{}", "äöü🚫x".repeat(100));
+    let folded = fold_lines(&description);
+    for line in folded.split_terminator("\r\n") {
+        assert!(line.len() <= MAX_LINE_OCTETS, "too long: {line}");
+    }
+    assert_eq!(unfold_lines(&folded), [description]);
+}
+
+#[test]
+fn folding_does_not_split_utf8_characters() {
+    let line = format!("SUMMARY:{}🚫", "a".repeat(65));
+    assert_eq!(line.len(), 77);
+    assert_eq!(
+        fold_lines(&line),
+        format!("SUMMARY:{}\r\n 🚫\r\n", "a".repeat(65))
+    );
+}
+
+#[test]
+fn unfold_examples() {
+    assert_eq!(
+        unfold_lines("SUMMARY:foo\r\n bar\r\n\tbaz\r\nLOCATION:1060\r\n"),
+        ["SUMMARY:foobarbaz", "LOCATION:1060"]
+    );
+}
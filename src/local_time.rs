@@ -0,0 +1,103 @@
+use chrono::{DateTime, LocalResult, NaiveDateTime, Offset as _, TimeDelta, TimeZone as _};
+use chrono_tz::Tz;
+
+/// Instant of a local time the same way calendar clients interpret `DTSTART;TZID=…`
+/// <https://www.rfc-editor.org/rfc/rfc5545#section-3.3.5>
+///
+/// A local time occurring twice (DST overlap) is the first occurrence.
+/// A local time which does not exist (DST gap) uses the offset from before the gap.
+/// Both return a warning describing what was assumed.
+pub fn resolve(timezone: Tz, local: NaiveDateTime) -> (DateTime<Tz>, Option<String>) {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(date_time) => (date_time, None),
+        LocalResult::Ambiguous(date_time, _) => {
+            let warning = format!(
+                "{} occurs twice in {}, the first one is used",
+                local.format("%Y-%m-%d %H:%M"),
+                timezone.name()
+            );
+            (date_time, Some(warning))
+        }
+        LocalResult::None => {
+            let date_time = (local - offset_before_gap(timezone, local))
+                .and_utc()
+                .with_timezone(&timezone);
+            let warning = format!(
+                "{} does not exist in {}, {} is used",
+                local.format("%Y-%m-%d %H:%M"),
+                timezone.name(),
+                date_time.format("%Y-%m-%d %H:%M")
+            );
+            (date_time, Some(warning))
+        }
+    }
+}
+
+/// Offset of the last local time before `local` which exists.
+/// Gaps are at most a day long (Pacific/Apia skipped 2011-12-30).
+fn offset_before_gap(timezone: Tz, local: NaiveDateTime) -> TimeDelta {
+    (1..=48)
+        .find_map(|hours| {
+            timezone
+                .from_local_datetime(&(local - TimeDelta::hours(hours)))
+                .latest()
+        })
+        .map_or_else(TimeDelta::zero, |before| {
+            TimeDelta::seconds(before.offset().fix().local_minus_utc().into())
+        })
+}
+
+/// Same local time in another week, day, … of the same zone
+pub fn shift(date_time: DateTime<Tz>, delta: TimeDelta) -> DateTime<Tz> {
+    resolve(date_time.timezone(), date_time.naive_local() + delta).0
+}
+
+#[cfg(test)]
+const fn date(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+#[test]
+fn unique_local_time() {
+    let (date_time, warning) = resolve(chrono_tz::Europe::Berlin, date(2020, 8, 22, 8, 30));
+    assert_eq!(date_time.naive_utc(), date(2020, 8, 22, 6, 30));
+    assert_eq!(warning, None);
+}
+
+#[test]
+fn overlap_is_the_first_occurrence() {
+    let (date_time, warning) = resolve(chrono_tz::Europe::Berlin, date(2020, 10, 25, 2, 30));
+    assert_eq!(date_time.naive_utc(), date(2020, 10, 25, 0, 30));
+    assert_eq!(
+        warning.as_deref(),
+        Some("2020-10-25 02:30 occurs twice in Europe/Berlin, the first one is used")
+    );
+}
+
+#[test]
+fn gap_uses_offset_before() {
+    let (date_time, warning) = resolve(chrono_tz::Europe::Berlin, date(2020, 3, 29, 2, 30));
+    assert_eq!(date_time.naive_utc(), date(2020, 3, 29, 1, 30));
+    assert_eq!(
+        warning.as_deref(),
+        Some("2020-03-29 02:30 does not exist in Europe/Berlin, 2020-03-29 03:30 is used")
+    );
+}
+
+#[test]
+fn skipped_day() {
+    let (date_time, warning) = resolve(chrono_tz::Pacific::Apia, date(2011, 12, 30, 12, 0));
+    assert_eq!(date_time.naive_utc(), date(2011, 12, 30, 22, 0));
+    assert!(warning.is_some());
+}
+
+#[test]
+fn shift_keeps_local_time() {
+    let before = resolve(chrono_tz::Europe::Berlin, date(2020, 10, 17, 8, 30)).0;
+    let after = shift(before, TimeDelta::weeks(2));
+    assert_eq!(after.naive_local(), date(2020, 10, 31, 8, 30));
+    assert_eq!(after - before, TimeDelta::weeks(2) + TimeDelta::hours(1));
+}
@@ -1,33 +1,139 @@
+use std::process::ExitCode;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::Parser as _;
 
 use crate::changestatus::{Changestatus, Changetype, write_change_summary};
+use crate::cli::Command;
+use crate::http_server::Calendars;
+use crate::renames::Renames;
+use crate::settings::Settings;
+use crate::subscriptions::Subscriptions;
 use crate::watchcat::Watchcat;
 
 mod apply_changes;
 mod apply_details;
+mod calendar_diff;
 mod changestatus;
+mod cli;
 mod events;
 mod generate_ics;
+mod http_server;
+mod ics_writer;
+mod local_time;
+mod messages;
 mod output_files;
+mod renames;
+mod revisions;
+mod series;
+mod settings;
+mod stable_hash;
+mod status_files;
+mod subscriptions;
 mod userconfig;
 mod userconfigs;
+mod validate;
+mod vtimezone;
 mod watchcat;
 
-fn main() {
-    output_files::ensure_directory().expect("should be able to create output directory");
+fn main() -> ExitCode {
+    let cli = cli::Cli::parse();
+    let mut settings = Settings {
+        eventfiles_dir: cli.eventfiles_dir,
+        userconfig_dir: cli.userconfig_dir,
+        calendars_dir: cli.calendars_dir,
+        dry_run: cli.dry_run,
+        timezone: cli.timezone,
+        time_format: cli.time_format,
+        change_tolerance: TimeDelta::minutes(cli.change_tolerance.into()),
+        status_dir: cli.status_dir,
+        report_file: cli.report_file,
+        renames: cli
+            .renames_file
+            .map(|path| Renames::load(&path).expect("should be able to read renames file"))
+            .unwrap_or_default(),
+        served_calendars: None,
+    };
+
+    let command = cli.command.unwrap_or(Command::Watch);
+    // Validation and dry runs only read and should not create anything
+    if !settings.dry_run && !matches!(command, Command::Validate) {
+        output_files::ensure_directory(&settings)
+            .expect("should be able to create output directory");
+        status_files::ensure_directory(&settings)
+            .expect("should be able to create status directory");
+    }
+
+    match command {
+        Command::Validate => validate::validate(&settings),
+        Command::BuildAll => build_all(&settings),
+        Command::Build { userconfig } => build(&settings, &userconfig),
+        Command::Watch => {
+            settings.served_calendars = cli.http_listen.map(|address| {
+                let calendars = Calendars::default();
+                output_files::publish_existing(&settings, &calendars)
+                    .expect("should be able to read existing calendars");
+                http_server::serve(&address, calendars.clone())
+                    .expect("should be able to serve HTTP");
+                calendars
+            });
+            watch(&settings)
+        }
+    }
+}
+
+fn build_all(settings: &Settings) -> ExitCode {
+    println!("Begin build all configs...");
+    let changes = match do_all(settings, &mut Subscriptions::default()) {
+        Ok(changes) => changes,
+        Err(err) => {
+            println!("failed to build all {err:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let failed = changes
+        .iter()
+        .any(|change| change.changetype == Changetype::Failed);
+    _ = write_change_summary(&mut std::io::stdout(), &changes, Changetype::ALL);
+    report(settings, &changes);
+    println!("Finished building all configs.");
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn build(settings: &Settings, userconfig_filename: &str) -> ExitCode {
+    let result = do_specific(settings, &mut Subscriptions::default(), userconfig_filename);
+    let exit_code = if result.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    };
+    let change = print_specific(userconfig_filename, result);
+    report(settings, &[change]);
+    exit_code
+}
+
+fn watch(settings: &Settings) -> ! {
     let mut stdout = std::io::stdout();
     println!("Begin build all configs...");
 
-    let all = userconfigs::load_all();
-    let changes = output_files::all_remove_rest(all)
+    let mut subscriptions = Subscriptions::default();
+    let changes = do_all(settings, &mut subscriptions)
         .expect("should be able to build all initial userconfigs");
-    _ = write_change_summary(&mut stdout, changes, Changetype::ALL);
+    _ = write_change_summary(&mut stdout, &changes, Changetype::ALL);
+    report(settings, &changes);
 
     println!("Finished building all configs. Engage watchcats...\n");
 
-    let event_watcher = Watchcat::new(events::FOLDER);
-    let userconfig_watcher = Watchcat::new(userconfigs::FOLDER);
+    let event_watcher = Watchcat::new(&settings.eventfiles_dir);
+    let userconfig_watcher = Watchcat::new(&settings.userconfig_dir);
 
     loop {
         let mut event_changes = event_watcher.get_changed_filenames();
@@ -36,32 +142,98 @@ fn main() {
             event_changes.append(&mut event_watcher.get_changed_filenames());
             println!("changed ({:3}): {event_changes:?}", event_changes.len());
 
-            match do_all() {
-                Ok(changes) => {
-                    _ = write_change_summary(&mut stdout, changes, Changetype::INTERESTING);
+            let affected = subscriptions.userconfigs_of_eventfiles(&event_changes);
+            println!("affected userconfigs ({:3})", affected.len());
+
+            let mut changes = Vec::new();
+            for filename in affected {
+                match do_specific(settings, &mut subscriptions, &filename) {
+                    Ok(change) => changes.push(change),
+                    Err((chat_id, err)) => {
+                        println!("{err:#}");
+                        changes.push(Changestatus::failed(filename, chat_id, &err));
+                    }
                 }
-                Err(err) => println!("failed to build all {err:#}"),
             }
+            _ = write_change_summary(&mut stdout, &changes, Changetype::INTERESTING);
+            report(settings, &changes);
         }
 
         for filename in userconfig_watcher.get_changed_filenames() {
             println!("userconfig changed {filename:>16}... ");
-            match do_specific(&filename) {
-                Ok(change) => println!("{:?} {}", change.changetype, change.name),
-                Err(err) => println!("{err:#}"),
-            }
+            let result = do_specific(settings, &mut subscriptions, &filename);
+            let change = print_specific(&filename, result);
+            report(settings, &[change]);
         }
 
         sleep(Duration::from_secs(5));
     }
 }
 
-fn do_all() -> anyhow::Result<Vec<Changestatus>> {
-    let all = userconfigs::load_all();
-    output_files::all_remove_rest(all)
+fn print_specific(userconfig_filename: &str, result: SpecificResult) -> Changestatus {
+    match result {
+        Ok(change) => {
+            println!("{:?} {}", change.changetype, change.name);
+            change
+        }
+        Err((chat_id, err)) => {
+            println!("{err:#}");
+            Changestatus::failed(userconfig_filename.to_owned(), chat_id, &err)
+        }
+    }
+}
+
+fn report(settings: &Settings, changes: &[Changestatus]) {
+    if let Some(path) = &settings.report_file {
+        let now = DateTime::<Utc>::from(SystemTime::now());
+        if let Err(err) = changestatus::append_change_report(path, changes, now) {
+            println!("failed to write report to {}: {err}", path.display());
+        }
+    }
+}
+
+fn do_all(
+    settings: &Settings,
+    subscriptions: &mut Subscriptions,
+) -> anyhow::Result<Vec<Changestatus>> {
+    let (all, mut failed) = userconfigs::load_all(settings);
+    *subscriptions = Subscriptions::new(&all);
+    let unloadable = failed
+        .iter()
+        .filter_map(|change| change.chat_id)
+        .collect::<Vec<_>>();
+    let mut changes = output_files::all_remove_rest(
+        settings,
+        all.into_iter().map(|(_, config)| config).collect(),
+        &unloadable,
+    )?;
+    changes.append(&mut failed);
+    Ok(changes)
 }
 
-fn do_specific(userconfig_filename: &str) -> anyhow::Result<Changestatus> {
-    let config = userconfigs::load_specific(userconfig_filename)?;
-    output_files::one(config)
+/// Result of building a single userconfig. Errors carry the chat id when it is known.
+type SpecificResult = Result<Changestatus, (Option<i64>, anyhow::Error)>;
+
+fn do_specific(
+    settings: &Settings,
+    subscriptions: &mut Subscriptions,
+    userconfig_filename: &str,
+) -> SpecificResult {
+    if !userconfigs::exists(settings, userconfig_filename) {
+        if let Some(chat_id) = subscriptions.remove(userconfig_filename) {
+            return output_files::remove(settings, chat_id, userconfig_filename.to_owned())
+                .map_err(|err| (Some(chat_id), err));
+        }
+        let chat_id = userconfigs::chat_id_of_filename(userconfig_filename);
+        return Err((
+            chat_id,
+            anyhow::anyhow!("userconfig {userconfig_filename} is gone and was never built"),
+        ));
+    }
+
+    let config = userconfigs::load_specific(settings, userconfig_filename)
+        .map_err(|err| (userconfigs::chat_id_of_filename(userconfig_filename), err))?;
+    subscriptions.update(userconfig_filename, &config);
+    let chat_id = config.chat.id;
+    output_files::one(settings, config).map_err(|err| (Some(chat_id), err))
 }
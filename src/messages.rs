@@ -0,0 +1,30 @@
+#![expect(clippy::non_ascii_literal)]
+
+use crate::userconfig::Language;
+
+/// Name of the calendar shown by calendar clients. The same in every language for now.
+pub fn calendar_name(language: Language, first_name: &str) -> String {
+    match language {
+        Language::German | Language::English => format!("@HAWHHCalendarBot ({first_name})"),
+    }
+}
+
+/// Description of an event the user added via the Telegram bot without an own description
+pub const fn added_event_description(language: Language) -> &'static str {
+    match language {
+        Language::German => {
+            "Dies ist eine zusätzliche Veranstaltung welche manuell von dir über den Telegram Bot hinzufügt wurde."
+        }
+        Language::English => {
+            "This is an additional event which you added manually via the Telegram bot."
+        }
+    }
+}
+
+/// Put in front of the title of removed events marked with [`crate::userconfig::RemovedEventMarks::emoji`].
+/// The same in every language for now.
+pub const fn removed_marker(language: Language) -> &'static str {
+    match language {
+        Language::German | Language::English => "🚫",
+    }
+}
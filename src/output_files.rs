@@ -1,183 +1,474 @@
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use anyhow::Context as _;
+use chrono::{DateTime, Utc};
 
-use crate::apply_changes::apply_changes;
+use crate::apply_changes::{Mismatches, apply_changes};
 use crate::apply_details::apply_details;
+use crate::calendar_diff::{CalendarDiff, write_calendar_diff};
 use crate::changestatus::{Changestatus, Changetype};
-use crate::events;
 use crate::generate_ics::{SoonToBeIcsEvent, generate_ics};
-use crate::userconfig::UserconfigFile;
+use crate::http_server::{self, Calendars};
+use crate::revisions::Revisions;
+use crate::settings::Settings;
+use crate::userconfig::{Userconfig, UserconfigFile};
+use crate::{events, messages, status_files};
 
-pub struct Buildresult {
-    pub changestatus: Changestatus,
-    pub filename: String,
+/// Calendars are written to a temporary file first which is renamed into place afterwards
+const TEMPORARY_PREFIX: &str = ".";
+const TEMPORARY_SUFFIX: &str = ".tmp";
+
+/// Existing calendars of a user
+struct ExistingCalendars {
+    changetype: Changetype,
+    /// Previous build to compare with
+    previous: Option<PathBuf>,
+    /// Calendars to be removed once the current one is written
+    superfluous: Vec<PathBuf>,
 }
 
-pub const FOLDER: &str = "calendars";
+/// Create the calendars directory and remove temporary files of interrupted writes
+pub fn ensure_directory(settings: &Settings) -> std::io::Result<()> {
+    fs::create_dir_all(&settings.calendars_dir)?;
+    remove_temporary_files(&settings.calendars_dir)
+}
 
-pub fn ensure_directory() -> std::io::Result<()> {
-    fs::create_dir_all(FOLDER)
+/// Remove temporary files of interrupted [`write_atomically`] calls
+pub fn remove_temporary_files(directory: &Path) -> std::io::Result<()> {
+    for maybe_entry in fs::read_dir(directory)? {
+        let entry = maybe_entry?;
+        let is_temporary = entry.file_name().to_str().is_some_and(|filename| {
+            filename.starts_with(TEMPORARY_PREFIX) && filename.ends_with(TEMPORARY_SUFFIX)
+        });
+        if is_temporary {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
 }
 
-pub fn one(content: UserconfigFile) -> anyhow::Result<Changestatus> {
+/// Publish the calendars written by previous runs so they are served while the first build is still running
+pub fn publish_existing(settings: &Settings, calendars: &Calendars) -> std::io::Result<()> {
+    for filename in get_existing_files(settings, "")? {
+        let Some((chat_id, suffix)) = http_server::parse_calendar_filename(&filename) else {
+            continue;
+        };
+        let path = settings.calendars_dir.join(&filename);
+        let content = fs::read_to_string(&path)?;
+        let last_modified = fs::metadata(&path)?.modified()?;
+        calendars.publish(chat_id, suffix, &content, last_modified.into());
+    }
+    Ok(())
+}
+
+pub fn one(settings: &Settings, content: UserconfigFile) -> anyhow::Result<Changestatus> {
     let user_id = content.chat.id;
-    one_internal(content)
-        .map(|buildresult| buildresult.changestatus)
+    one_internal(settings, content)
         .with_context(|| format!("Failed to build calendar for {user_id}"))
 }
 
-fn one_internal(content: UserconfigFile) -> anyhow::Result<Buildresult> {
+/// Remove the calendars of a user whose userconfig is gone
+pub fn remove(settings: &Settings, chat_id: i64, name: String) -> anyhow::Result<Changestatus> {
+    if let Some(calendars) = &settings.served_calendars {
+        calendars.remove(chat_id);
+    }
+
+    let existing = get_existing_files(settings, &format!("{chat_id}-"))
+        .context("failed to read existing calendars of user")?;
+
+    let mut changetype = Changetype::Skipped;
+    for filename in existing {
+        let path = settings.calendars_dir.join(filename);
+        remove_calendar(settings, &name, &path)
+            .context("failed to remove calendar of removed user")?;
+        changetype = Changetype::Removed;
+    }
+
+    if !settings.dry_run {
+        status_files::remove(settings, chat_id).context("failed to remove status of user")?;
+    }
+
+    Ok(Changestatus {
+        chat_id: Some(chat_id),
+        ..Changestatus::new(name, changetype)
+    })
+}
+
+fn one_internal(settings: &Settings, mut content: UserconfigFile) -> anyhow::Result<Changestatus> {
     let user_id = content.chat.id;
     let first_name = content.chat.first_name;
     let ics_filename = format!("{user_id}-{}.ics", content.config.calendarfile_suffix);
-    let path = Path::new(FOLDER).join(&ics_filename);
+    let path = settings.calendars_dir.join(&ics_filename);
 
-    let mut changetype = Changetype::Same;
+    let existing = tidy_existing_calendars(settings, user_id, &ics_filename)?;
+    let mut changetype = existing.changetype;
+    let current_content = existing
+        .previous
+        .and_then(|path| fs::read_to_string(path).ok());
 
-    let existing = get_existing_files(&format!("{user_id}-"))
-        .context("failed to read existing calendars of user")?;
+    let mut warnings = Vec::new();
+    let (mut user_events, skipped_eventfiles) =
+        load_user_events(settings, &content.config, &mut warnings);
 
-    match existing.len() {
-        1 => {
-            if existing[0] != ics_filename {
-                let existing_path = Path::new(FOLDER).join(&existing[0]);
-                fs::rename(existing_path, &path).context("failed to rename old calendar")?;
-                changetype = Changetype::Moved;
-            }
+    let mismatches = apply_userconfig(
+        settings,
+        &mut user_events,
+        &mut content.config,
+        &mut warnings,
+    );
+    for warning in &warnings {
+        println!("warning {first_name}: {warning}");
+    }
+
+    if user_events.is_empty() {
+        let changetype =
+            remove_all_calendars(settings, user_id, &first_name, &existing.superfluous, &path)?;
+        if !settings.dry_run {
+            status_files::write(settings, user_id, &mismatches)
+                .context("failed to write status of user")?;
         }
-        0 => {}
-        _ => {
-            for filename in existing {
-                let existing_path = Path::new(FOLDER).join(filename);
-                fs::remove_file(existing_path)
-                    .context("failed to remove superfluous calendars of user")?;
-                changetype = Changetype::Removed;
-            }
+        return Ok(Changestatus {
+            chat_id: Some(user_id),
+            skipped_eventfiles,
+            unmatched_changes: mismatches.unmatched,
+            fuzzy_matched_changes: mismatches.fuzzy,
+            renamed_events: content.config.renamed_events,
+            warnings,
+            ..Changestatus::new(first_name, changetype)
+        });
+    }
+    user_events.sort_by_cached_key(|event| event.start_time);
+
+    let revisions = current_content
+        .as_deref()
+        .map(Revisions::from_ics)
+        .unwrap_or_default();
+    let now = DateTime::<Utc>::from(SystemTime::now());
+    let ics_content = generate_ics(
+        &messages::calendar_name(content.config.language, &first_name),
+        &user_events,
+        content.config.recurring_events,
+        content.config.timezone.unwrap_or(settings.timezone),
+        content.config.time_format.unwrap_or(settings.time_format),
+        &revisions,
+        now,
+    );
+
+    if let Some(current_content) = &current_content {
+        if ics_content != *current_content {
+            changetype = Changetype::Changed;
         }
+    } else {
+        changetype = Changetype::Added;
     }
 
-    let mut user_events = Vec::new();
-    let mut event_keys = content.config.events.keys().collect::<Vec<_>>();
-    event_keys.sort();
-    for name in event_keys {
-        match load_and_parse_events(name) {
-            Ok(mut events) => user_events.append(&mut events),
-            Err(err) => println!("skip event {name:32} {err:#}"),
+    if settings.dry_run {
+        let diff = CalendarDiff::new(current_content.as_deref().unwrap_or_default(), &ics_content);
+        if !diff.is_empty() {
+            _ = write_calendar_diff(&mut std::io::stdout(), &first_name, &diff);
         }
+    } else {
+        if matches!(changetype, Changetype::Changed | Changetype::Added) {
+            write_atomically(&path, &ics_content).context("failed to write ics file content")?;
+        }
+        status_files::write(settings, user_id, &mismatches)
+            .context("failed to write status of user")?;
     }
 
-    if user_events.is_empty() {
+    // Only remove them now so there is always a calendar of the user even when interrupted
+    for superfluous in &existing.superfluous {
+        remove_calendar(settings, &first_name, superfluous)
+            .context("failed to remove superfluous calendars of user")?;
+    }
+
+    if let Some(calendars) = &settings.served_calendars {
+        let last_modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_or(now, DateTime::from);
+        calendars.publish(
+            user_id,
+            &content.config.calendarfile_suffix,
+            &ics_content,
+            last_modified,
+        );
+    }
+
+    Ok(Changestatus {
+        chat_id: Some(user_id),
+        events: user_events.len(),
+        skipped_eventfiles,
+        unmatched_changes: mismatches.unmatched,
+        fuzzy_matched_changes: mismatches.fuzzy,
+        renamed_events: content.config.renamed_events,
+        warnings,
+        ..Changestatus::new(first_name, changetype)
+    })
+}
+
+/// Without events the user has no calendar at all
+fn remove_all_calendars(
+    settings: &Settings,
+    user_id: i64,
+    first_name: &str,
+    superfluous: &[PathBuf],
+    path: &Path,
+) -> anyhow::Result<Changetype> {
+    if let Some(calendars) = &settings.served_calendars {
+        calendars.remove(user_id);
+    }
+    let mut changetype = Changetype::Skipped;
+    for path in superfluous.iter().map(PathBuf::as_path).chain([path]) {
         if path.exists() {
-            fs::remove_file(&path).context("failed to remove calendar with now 0 events")?;
+            remove_calendar(settings, first_name, path)
+                .context("failed to remove calendar with now 0 events")?;
             changetype = Changetype::Removed;
-        } else {
-            changetype = Changetype::Skipped;
         }
-
-        return Ok(Buildresult {
-            filename: ics_filename,
-            changestatus: Changestatus {
-                name: first_name,
-                changetype,
-            },
-        });
     }
+    Ok(changetype)
+}
 
-    apply_changes(
-        &mut user_events,
-        content.config.changes,
-        content.config.removed_events,
-    )
-    .context("failed to apply changes")?;
+/// Apply the changes and details of the userconfig to the events
+fn apply_userconfig(
+    settings: &Settings,
+    events: &mut Vec<SoonToBeIcsEvent>,
+    config: &mut Userconfig,
+    warnings: &mut Vec<String>,
+) -> Mismatches {
+    let changes = std::mem::take(&mut config.changes)
+        .into_iter()
+        .map(|change| (settings.renames.resolve(&change.name).to_owned(), change))
+        .collect();
+    let mismatches = apply_changes(
+        events,
+        changes,
+        config.language,
+        &config.removed_events,
+        settings.change_tolerance,
+        settings.timezone,
+        warnings,
+    );
 
-    for event in &mut user_events {
-        if let Some(details) = content.config.events.get(&event.name) {
+    for event in events.iter_mut() {
+        if let Some(details) = config.events.get(&event.name) {
             apply_details(event, details);
         } else {
-            // IE changed from IE2-IC/01 to IE2-IC-01 for ics files
-            // the old name was used and is not yet updated in the TelegramBot
-            // skip here and wait for the user to update them in the TelegramBot
+            // The eventfile contains events of another name like IE2-IC/01 instead of IE2-IC-01.
+            // Such renames belong into the renames file.
         }
     }
 
-    user_events.sort_by_cached_key(|event| event.start_time);
-    let ics_content = generate_ics(&first_name, &user_events);
+    mismatches
+}
 
-    if let Ok(current_content) = fs::read_to_string(&path) {
-        if ics_content != current_content {
-            changetype = Changetype::Changed;
+/// Move the calendar of the user to its current filename.
+/// When the user has multiple calendars the superfluous ones are only listed and removed later.
+///
+/// On dry runs nothing is moved so the previous build is not always at the current filename.
+fn tidy_existing_calendars(
+    settings: &Settings,
+    user_id: i64,
+    ics_filename: &str,
+) -> anyhow::Result<ExistingCalendars> {
+    let path = settings.calendars_dir.join(ics_filename);
+
+    let existing = get_existing_files(settings, &format!("{user_id}-"))
+        .context("failed to read existing calendars of user")?;
+
+    match existing.as_slice() {
+        [] => Ok(ExistingCalendars {
+            changetype: Changetype::Same,
+            previous: Some(path),
+            superfluous: Vec::new(),
+        }),
+        [single] if single == ics_filename => Ok(ExistingCalendars {
+            changetype: Changetype::Same,
+            previous: Some(path),
+            superfluous: Vec::new(),
+        }),
+        [single] => {
+            let existing_path = settings.calendars_dir.join(single);
+            if settings.dry_run {
+                return Ok(ExistingCalendars {
+                    changetype: Changetype::Moved,
+                    previous: Some(existing_path),
+                    superfluous: Vec::new(),
+                });
+            }
+
+            // A rename within a directory is atomic so there is always exactly one calendar
+            fs::rename(existing_path, &path).context("failed to rename old calendar")?;
+            sync_directory(&settings.calendars_dir).context("failed to sync renamed calendar")?;
+            Ok(ExistingCalendars {
+                changetype: Changetype::Moved,
+                previous: Some(path),
+                superfluous: Vec::new(),
+            })
         }
-    } else {
-        changetype = Changetype::Added;
+        _ => Ok(ExistingCalendars {
+            changetype: Changetype::Removed,
+            previous: existing
+                .iter()
+                .any(|filename| filename == ics_filename)
+                .then_some(path),
+            superfluous: existing
+                .iter()
+                .filter(|filename| *filename != ics_filename)
+                .map(|filename| settings.calendars_dir.join(filename))
+                .collect(),
+        }),
     }
+}
+
+/// Write to a temporary file in the same directory which is renamed into place once it is completely on disk.
+/// That way clients never get a truncated calendar even when the process is killed or the disk is full.
+pub fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    let directory = path.parent().unwrap_or_else(|| Path::new("."));
+    let filename = path
+        .file_name()
+        .and_then(|filename| filename.to_str())
+        .expect("calendar path should have a UTF8 filename");
+    let temporary = directory.join(format!("{TEMPORARY_PREFIX}{filename}{TEMPORARY_SUFFIX}"));
 
-    if matches!(changetype, Changetype::Changed | Changetype::Added) {
-        fs::write(&path, &ics_content).context("failed to write ics file content")?;
+    let result = File::create(&temporary).and_then(|mut file| {
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(err) = result {
+        _ = fs::remove_file(&temporary);
+        return Err(err);
     }
 
-    Ok(Buildresult {
-        filename: ics_filename,
-        changestatus: Changestatus {
-            name: first_name,
-            changetype,
-        },
-    })
+    fs::rename(&temporary, path)?;
+    sync_directory(directory)
 }
 
-fn load_and_parse_events(name: &str) -> anyhow::Result<Vec<SoonToBeIcsEvent>> {
+/// Persist renames and removals within the directory
+fn sync_directory(directory: &Path) -> std::io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+/// Remove the calendar file or only report its events as removed on dry runs
+fn remove_calendar(settings: &Settings, name: &str, path: &Path) -> anyhow::Result<()> {
+    if settings.dry_run {
+        let current_content = fs::read_to_string(path).unwrap_or_default();
+        let diff = CalendarDiff::new(&current_content, "");
+        _ = write_calendar_diff(&mut std::io::stdout(), name, &diff);
+        return Ok(());
+    }
+
+    fs::remove_file(path)?;
+    sync_directory(path.parent().unwrap_or_else(|| Path::new(".")))?;
+    Ok(())
+}
+
+/// Events of all the given eventfiles and the names of the ones which could not be read
+fn load_user_events(
+    settings: &Settings,
+    config: &Userconfig,
+    warnings: &mut Vec<String>,
+) -> (Vec<SoonToBeIcsEvent>, Vec<String>) {
+    let mut names = config.events.keys().collect::<Vec<_>>();
+    names.sort();
+
+    let mut user_events = Vec::new();
+    let mut skipped_eventfiles = Vec::new();
+    for name in names {
+        match load_and_parse_events(settings, name, warnings) {
+            Ok(mut events) => user_events.append(&mut events),
+            Err(err) => {
+                println!("skip event {name:32} {err:#}");
+                skipped_eventfiles.push((*name).clone());
+            }
+        }
+    }
+    (user_events, skipped_eventfiles)
+}
+
+fn load_and_parse_events(
+    settings: &Settings,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> anyhow::Result<Vec<SoonToBeIcsEvent>> {
+    let source = events::source(name);
     let mut result = Vec::new();
-    for event in events::read(name)? {
-        result.push(event.into());
+    for mut event in events::read(settings, name)? {
+        let name = settings.renames.resolve(&event.name).to_owned();
+        event.name = name;
+        result.push(event.into_ics_event(&source, settings.timezone, warnings));
     }
     Ok(result)
 }
 
-pub fn all_remove_rest(list: Vec<UserconfigFile>) -> anyhow::Result<Vec<Changestatus>> {
+/// Build every userconfig and remove calendars and status files of users without one.
+/// Userconfigs which failed to load are given via their `unloadable` chat ids and keep their files.
+pub fn all_remove_rest(
+    settings: &Settings,
+    list: Vec<UserconfigFile>,
+    unloadable: &[i64],
+) -> anyhow::Result<Vec<Changestatus>> {
     let mut changestati: Vec<Changestatus> = Vec::new();
-    let mut created_files: Vec<String> = Vec::new();
+    let mut chat_ids: Vec<i64> = unloadable.to_vec();
 
     for content in list {
         let chat_id = content.chat.id;
-        match one_internal(content) {
-            Ok(filechange) => {
-                changestati.push(filechange.changestatus);
-                created_files.push(filechange.filename);
+        let name = content.chat.first_name.clone();
+        chat_ids.push(chat_id);
+        match one_internal(settings, content) {
+            Ok(changestatus) => changestati.push(changestatus),
+            Err(error) => {
+                println!("Failed to build calendar for {chat_id}: {error:#}");
+                changestati.push(Changestatus::failed(name, Some(chat_id), &error));
             }
-            Err(error) => println!("Failed to build calendar for {chat_id}: {error:#}"),
         }
     }
 
-    let existing = get_existing_files("").context("failed to read calendars dir for cleanup")?;
+    if let Some(calendars) = &settings.served_calendars {
+        calendars.retain(&chat_ids);
+    }
+    status_files::retain(settings, &chat_ids)
+        .context("failed to remove status files of removed users")?;
+
+    let existing =
+        get_existing_files(settings, "").context("failed to read calendars dir for cleanup")?;
 
     for filename in existing {
-        if created_files.contains(&filename) {
+        // Calendars of known users are tidied while building them.
+        // When that or loading their userconfig failed their previous calendar is kept.
+        if chat_ids
+            .iter()
+            .any(|chat_id| filename.starts_with(&format!("{chat_id}-")))
+        {
             continue;
         }
 
-        let path = Path::new(FOLDER).join(&filename);
-        fs::remove_file(path)
+        let path = settings.calendars_dir.join(&filename);
+        remove_calendar(settings, &filename, &path)
             .with_context(|| format!("failed to remove superfluous calendar file {filename}"))?;
 
-        changestati.push(Changestatus {
-            name: filename,
-            changetype: Changetype::Removed,
-        });
+        changestati.push(Changestatus::new(filename, Changetype::Removed));
     }
 
     Ok(changestati)
 }
 
-fn get_existing_files(starts_with: &str) -> std::io::Result<Vec<String>> {
+fn get_existing_files(settings: &Settings, starts_with: &str) -> std::io::Result<Vec<String>> {
+    let entries = match fs::read_dir(&settings.calendars_dir) {
+        Ok(entries) => entries,
+        // Dry runs do not create the directory
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
     let mut list: Vec<String> = Vec::new();
-    for maybe_entry in fs::read_dir(FOLDER)? {
+    for maybe_entry in entries {
         let filename = maybe_entry?
             .file_name()
             .into_string()
             .expect("filename should be UTF8");
 
-        if filename.starts_with(starts_with) {
+        if filename.starts_with(starts_with) && !filename.starts_with(TEMPORARY_PREFIX) {
             list.push(filename);
         }
     }
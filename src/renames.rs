@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context as _;
+use serde::Deserialize;
+
+use crate::userconfig::Userconfig;
+
+/// Old event names mapped to their current name like `IE2-IC/01` to `IE2-IC-01`
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Renames(HashMap<String, String>);
+
+impl Renames {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path).context("failed to read")?;
+        serde_json::from_str(&content).context("failed to parse")
+    }
+
+    /// Current name of the event. Follows names which were renamed multiple times.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        let mut current = name;
+        // Limited so a cycle in the table can not loop forever
+        for _ in 0..self.0.len() {
+            match self.0.get(current) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        current
+    }
+
+    /// Use the current names for the events of the userconfig.
+    /// Returns the old names which were used by events or changes, sorted.
+    ///
+    /// Details of the current name win when both names are in the userconfig.
+    /// Changes keep their name so they are reported the way the user knows them.
+    /// They are matched via [`Self::resolve`].
+    pub fn apply(&self, config: &mut Userconfig) -> Vec<String> {
+        let mut used = Vec::new();
+
+        let mut renamed_details = Vec::new();
+        for (name, details) in std::mem::take(&mut config.events) {
+            let current = self.resolve(&name);
+            if current == name {
+                config.events.insert(name, details);
+            } else {
+                renamed_details.push((current.to_owned(), details));
+                used.push(name);
+            }
+        }
+        for (current, details) in renamed_details {
+            config.events.entry(current).or_insert(details);
+        }
+
+        for change in &config.changes {
+            if self.resolve(&change.name) != change.name {
+                used.push(change.name.clone());
+            }
+        }
+
+        used.sort();
+        used.dedup();
+        used
+    }
+}
+
+#[cfg(test)]
+fn create_renames() -> Renames {
+    serde_json::from_str(
+        r#"{"IE2-IC/01": "IE2-IC-01", "BTI5-VS": "BTI5-VS/01", "BTI5-VS/01": "BTI5-VS-01"}"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn resolve_examples() {
+    let renames = create_renames();
+    assert_eq!(renames.resolve("IE2-IC/01"), "IE2-IC-01");
+    assert_eq!(renames.resolve("IE2-IC-01"), "IE2-IC-01");
+    assert_eq!(renames.resolve("BTI5-VS"), "BTI5-VS-01");
+    assert_eq!(renames.resolve("BTI1-TI"), "BTI1-TI");
+}
+
+#[test]
+fn cycle_ends() {
+    let renames: Renames = serde_json::from_str(r#"{"A": "B", "B": "A"}"#).unwrap();
+    assert_eq!(renames.resolve("A"), "A");
+}
+
+#[test]
+fn apply_to_userconfig() {
+    let mut config: Userconfig = serde_json::from_str(
+        r#"{
+            "calendarfileSuffix": "123qwe",
+            "events": {"IE2-IC/01": {"alias": "old"}, "IE2-IC-01": {"alias": "current"}, "BTI5-VS": {}, "BTI1-TI": {}},
+            "changes": [{"name": "BTI5-VS", "date": "2020-04-09T06:15", "remove": true}]
+        }"#,
+    )
+    .unwrap();
+    let used = create_renames().apply(&mut config);
+
+    assert_eq!(used, ["BTI5-VS", "IE2-IC/01"]);
+    let mut names = config.events.keys().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["BTI1-TI", "BTI5-VS-01", "IE2-IC-01"]);
+    assert_eq!(config.events["IE2-IC-01"].alias.as_deref(), Some("current"));
+    assert_eq!(config.changes[0].name, "BTI5-VS");
+}
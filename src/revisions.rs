@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::ics_writer::unfold_lines;
+use crate::stable_hash::fnv1a;
+
+const LAST_MODIFIED_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision {
+    pub sequence: u32,
+    pub last_modified: DateTime<Utc>,
+    fingerprint: u64,
+}
+
+/// SEQUENCE and LAST-MODIFIED of the VEVENTs of a previous build by their UID (and RECURRENCE-ID).
+///
+/// Nothing is persisted besides the ics file itself.
+/// The fingerprint is calculated from every VEVENT content line except SEQUENCE and LAST-MODIFIED.
+#[derive(Debug, Default)]
+pub struct Revisions(HashMap<String, Revision>);
+
+impl Revision {
+    pub fn write_content_lines(&self, output: &mut String) {
+        _ = writeln!(output, "SEQUENCE:{}", self.sequence);
+        _ = writeln!(
+            output,
+            "LAST-MODIFIED:{}",
+            self.last_modified.format(LAST_MODIFIED_FORMAT)
+        );
+    }
+}
+
+impl Revisions {
+    pub fn from_ics(ics: &str) -> Self {
+        let mut revisions = HashMap::new();
+
+        let mut content: Option<String> = None;
+        let mut sequence = 0;
+        let mut last_modified = None;
+
+        for line in unfold_lines(ics) {
+            if line == "BEGIN:VEVENT" {
+                content = Some(String::new());
+                sequence = 0;
+                last_modified = None;
+            } else if line == "END:VEVENT" {
+                if let Some(content) = content.take()
+                    && let Some(key) = vevent_key(&content)
+                    && let Some(last_modified) = last_modified.take()
+                {
+                    revisions.insert(
+                        key,
+                        Revision {
+                            sequence,
+                            last_modified,
+                            fingerprint: fnv1a(content.as_bytes()),
+                        },
+                    );
+                }
+            } else if let Some(content) = &mut content {
+                if let Some(value) = line.strip_prefix("SEQUENCE:") {
+                    sequence = value.parse().unwrap_or_default();
+                } else if let Some(value) = line.strip_prefix("LAST-MODIFIED:") {
+                    last_modified = NaiveDateTime::parse_from_str(value, LAST_MODIFIED_FORMAT)
+                        .ok()
+                        .map(|naive| naive.and_utc());
+                } else {
+                    *content += &line;
+                    *content += "\n";
+                }
+            }
+        }
+
+        Self(revisions)
+    }
+
+    /// Revision of the VEVENT with the given content lines.
+    /// SEQUENCE is increased and LAST-MODIFIED is set to now when the content differs from the previous build.
+    pub fn next(&self, content: &str, now: DateTime<Utc>) -> Revision {
+        let fingerprint = fnv1a(content.as_bytes());
+        match vevent_key(content).and_then(|key| self.0.get(&key)) {
+            Some(previous) if previous.fingerprint == fingerprint => *previous,
+            Some(previous) => Revision {
+                sequence: previous.sequence.saturating_add(1),
+                last_modified: now,
+                fingerprint,
+            },
+            None => Revision {
+                sequence: 0,
+                last_modified: now,
+                fingerprint,
+            },
+        }
+    }
+}
+
+/// The UID identifies a VEVENT. Overrides of a recurring VEVENT share its UID and are told apart by their RECURRENCE-ID.
+pub fn vevent_key(content: &str) -> Option<String> {
+    let uid = content.lines().find_map(|line| line.strip_prefix("UID:"))?;
+    let recurrence_id = content
+        .lines()
+        .find(|line| line.starts_with("RECURRENCE-ID"));
+    Some(recurrence_id.map_or_else(|| uid.to_owned(), |line| format!("{uid}\n{line}")))
+}
+
+#[cfg(test)]
+const fn timestamp(hour: u32) -> DateTime<Utc> {
+    chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+        .unwrap()
+        .and_hms_opt(hour, 0, 0)
+        .unwrap()
+        .and_utc()
+}
+
+#[cfg(test)]
+fn build_ics(content: &str, revision: Revision) -> String {
+    let mut ics = String::from("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n");
+    let mut lines = content.to_owned();
+    revision.write_content_lines(&mut lines);
+    ics += &lines.replace('\n', "\r\n");
+    ics += "END:VEVENT\r\nEND:VCALENDAR\r\n";
+    ics
+}
+
+#[test]
+fn new_event_starts_with_sequence_zero() {
+    let revision = Revisions::default().next("UID:a\n", timestamp(8));
+    assert_eq!(revision.sequence, 0);
+    assert_eq!(revision.last_modified, timestamp(8));
+}
+
+#[test]
+fn unchanged_event_keeps_revision() {
+    let previous = Revisions::default().next("UID:a\nSUMMARY:foo\n", timestamp(8));
+    let ics = build_ics("UID:a\nSUMMARY:foo\n", previous);
+
+    let revision = Revisions::from_ics(&ics).next("UID:a\nSUMMARY:foo\n", timestamp(10));
+    assert_eq!(revision, previous);
+}
+
+#[test]
+fn changed_event_increases_sequence() {
+    let previous = Revisions::default().next("UID:a\nSUMMARY:foo\n", timestamp(8));
+    let ics = build_ics("UID:a\nSUMMARY:foo\n", previous);
+    let revisions = Revisions::from_ics(&ics);
+
+    let revision = revisions.next("UID:a\nSUMMARY:bar\n", timestamp(10));
+    assert_eq!(revision.sequence, 1);
+    assert_eq!(revision.last_modified, timestamp(10));
+
+    let ics = build_ics("UID:a\nSUMMARY:bar\n", revision);
+    let revision = Revisions::from_ics(&ics).next("UID:a\nSUMMARY:baz\n", timestamp(12));
+    assert_eq!(revision.sequence, 2);
+    assert_eq!(revision.last_modified, timestamp(12));
+}
+
+#[test]
+fn overrides_have_their_own_revision() {
+    let master = Revisions::default().next("UID:a\nSUMMARY:foo\n", timestamp(8));
+    let recurrence = Revisions::default().next(
+        "UID:a\nRECURRENCE-ID:20200822T083000\nSUMMARY:bar\n",
+        timestamp(8),
+    );
+    let mut ics = build_ics("UID:a\nSUMMARY:foo\n", master);
+    ics += &build_ics(
+        "UID:a\nRECURRENCE-ID:20200822T083000\nSUMMARY:bar\n",
+        recurrence,
+    );
+    let revisions = Revisions::from_ics(&ics);
+
+    let revision = revisions.next("UID:a\nSUMMARY:foo\n", timestamp(10));
+    assert_eq!(revision, master);
+
+    let revision = revisions.next(
+        "UID:a\nRECURRENCE-ID:20200822T083000\nSUMMARY:baz\n",
+        timestamp(10),
+    );
+    assert_eq!(revision.sequence, 1);
+}
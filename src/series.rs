@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike as _, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+
+use crate::generate_ics::SoonToBeIcsEvent;
+use crate::local_time;
+
+pub enum Component<'a> {
+    Single(&'a SoonToBeIcsEvent),
+    Series(Box<Series<'a>>),
+}
+
+/// Weekly occurrences of an event combined into one RRULE
+pub struct Series<'a> {
+    /// Identity of the series which stays the same when occurrences are added or removed
+    pub identity: String,
+    /// First occurrence as described by the rule. Every other occurrence looks the same a week later.
+    pub first: SoonToBeIcsEvent,
+    /// Amount of weeks from the first to the last occurrence
+    pub count: usize,
+    /// Weeks within the series without an occurrence in local time of the first occurrence
+    pub exdates: Vec<NaiveDateTime>,
+    /// Occurrences which differ from the rule by the local start time the rule would give them (RECURRENCE-ID)
+    pub overrides: Vec<(NaiveDateTime, &'a SoonToBeIcsEvent)>,
+}
+
+const WEEK: TimeDelta = TimeDelta::weeks(1);
+
+/// Combine occurrences of the same event on the same weekday and time into series.
+/// Everything not being part of a series stays a single event.
+///
+/// Weeks are counted in local time so the rule repeats the local time of the first occurrence.
+///
+/// Components are in order of their first event.
+pub fn find(events: &[SoonToBeIcsEvent]) -> Vec<Component<'_>> {
+    let mut clusters: HashMap<(&str, &str, Weekday, NaiveTime), Vec<&SoonToBeIcsEvent>> =
+        HashMap::new();
+    let mut cluster_order = Vec::new();
+    for event in events {
+        let key = (
+            event.source.as_str(),
+            event.name.as_str(),
+            event.original_start_time.weekday(),
+            event.original_start_time.time(),
+        );
+        let cluster = clusters.entry(key).or_default();
+        if cluster.is_empty() {
+            cluster_order.push(key);
+        }
+        cluster.push(event);
+    }
+
+    let mut components = Vec::new();
+    for key in cluster_order {
+        let mut cluster = clusters
+            .remove(&key)
+            .expect("every ordered key has a cluster");
+        cluster.sort_by_key(|event| event.original_start_time);
+
+        let mut occurrences = Vec::new();
+        for event in cluster {
+            if occurrences.last().is_some_and(|last: &&SoonToBeIcsEvent| {
+                last.original_start_time == event.original_start_time
+            }) {
+                // Two occurrences at the same time can not be described by a weekly rule
+                components.push(Component::Single(event));
+            } else {
+                occurrences.push(event);
+            }
+        }
+
+        if let [single] = occurrences.as_slice() {
+            components.push(Component::Single(single));
+        } else {
+            components.push(Component::Series(Box::new(create_series(&occurrences))));
+        }
+    }
+
+    components.sort_by_key(|component| match component {
+        Component::Single(event) => event.start_time,
+        Component::Series(series) => series.first.start_time,
+    });
+    components
+}
+
+fn create_series<'a>(occurrences: &[&'a SoonToBeIcsEvent]) -> Series<'a> {
+    let template = most_common_shape(occurrences);
+    let series_start = occurrences[0].original_start_time.naive_local();
+    let first = shifted(
+        template,
+        series_start - template.original_start_time.naive_local(),
+    );
+
+    let weeks_from_start = |event: &SoonToBeIcsEvent| {
+        (event.original_start_time.naive_local() - series_start).num_weeks()
+    };
+    let last_week = occurrences
+        .last()
+        .map_or(0, |event| weeks_from_start(event));
+
+    let mut exdates = Vec::new();
+    let mut overrides = Vec::new();
+    let mut occurrences = occurrences.iter().peekable();
+    for week in 0..=last_week {
+        let rule_start =
+            first.start_time.naive_local() + WEEK * i32::try_from(week).unwrap_or(i32::MAX);
+        match occurrences.next_if(|event| weeks_from_start(event) == week) {
+            Some(event) if same_shape(&first, event) => {}
+            Some(event) => overrides.push((rule_start, *event)),
+            None => exdates.push(rule_start),
+        }
+    }
+
+    Series {
+        identity: format!(
+            "{}\n{}\nweekly {} {}",
+            first.source,
+            first.name,
+            series_start.weekday(),
+            series_start.time()
+        ),
+        count: usize::try_from(last_week).unwrap_or_default() + 1,
+        first,
+        exdates,
+        overrides,
+    }
+}
+
+/// The occurrence which looks like most of the others. On ties the earliest one.
+fn most_common_shape<'a>(occurrences: &[&'a SoonToBeIcsEvent]) -> &'a SoonToBeIcsEvent {
+    let mut best = occurrences[0];
+    let mut best_count = 0;
+    for candidate in occurrences {
+        let count = occurrences
+            .iter()
+            .filter(|other| same_shape(candidate, other))
+            .count();
+        if count > best_count {
+            best = candidate;
+            best_count = count;
+        }
+    }
+    best
+}
+
+/// Same event besides being in another week
+fn same_shape(event: &SoonToBeIcsEvent, other: &SoonToBeIcsEvent) -> bool {
+    *event
+        == shifted(
+            other,
+            event.original_start_time.naive_local() - other.original_start_time.naive_local(),
+        )
+}
+
+/// Every time of the event moved by `delta` in its local time
+fn shifted(event: &SoonToBeIcsEvent, delta: TimeDelta) -> SoonToBeIcsEvent {
+    let mut event = event.clone();
+    event.original_start_time = local_time::shift(event.original_start_time, delta);
+    event.start_time = local_time::shift(event.start_time, delta);
+    event.end_time = local_time::shift(event.end_time, delta);
+    event
+}
+
+#[cfg(test)]
+fn create_event(day: u32) -> SoonToBeIcsEvent {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 4, day)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap()
+        .and_local_timezone(chrono_tz::Europe::Berlin)
+        .unwrap();
+    SoonToBeIcsEvent {
+        name: "BTI5-VS".to_owned(),
+        pretty_name: "BTI5-VS".to_owned(),
+        source: "BTI5-VS".to_owned(),
+        original_start_time: start_time,
+        status: crate::generate_ics::EventStatus::Confirmed,
+        start_time,
+        end_time: start_time + TimeDelta::hours(3),
+        alerts: None,
+        description: String::new(),
+        location: "1060".to_owned(),
+        categories: Vec::new(),
+        color: None,
+        transparent: false,
+    }
+}
+
+#[cfg(test)]
+fn unwrap_series<'c, 'a>(component: &'c Component<'a>) -> &'c Series<'a> {
+    match component {
+        Component::Series(series) => series,
+        Component::Single(event) => panic!("should be a series: {event:?}"),
+    }
+}
+
+#[test]
+fn weekly_events_become_series() {
+    let events = vec![create_event(2), create_event(9), create_event(16)];
+    let components = find(&events);
+    assert_eq!(components.len(), 1);
+
+    let series = unwrap_series(&components[0]);
+    assert_eq!(series.count, 3);
+    assert_eq!(series.first, events[0]);
+    assert!(series.exdates.is_empty());
+    assert!(series.overrides.is_empty());
+}
+
+#[test]
+fn single_event_stays_single() {
+    let mut other_time = create_event(9);
+    other_time.original_start_time += TimeDelta::hours(2);
+    let events = vec![create_event(2), other_time];
+
+    let components = find(&events);
+    assert_eq!(components.len(), 2);
+    assert!(matches!(components[0], Component::Single(_)));
+    assert!(matches!(components[1], Component::Single(_)));
+}
+
+#[test]
+fn missing_week_is_excluded() {
+    let events = vec![create_event(2), create_event(16), create_event(23)];
+    let components = find(&events);
+    let series = unwrap_series(&components[0]);
+    assert_eq!(series.count, 4);
+    assert_eq!(series.exdates, [create_event(9).start_time.naive_local()]);
+}
+
+#[test]
+fn changed_occurrence_becomes_override() {
+    let mut moved = create_event(9);
+    moved.location = "1100".to_owned();
+    moved.start_time += TimeDelta::minutes(15);
+    let events = vec![create_event(2), moved, create_event(16)];
+
+    let components = find(&events);
+    let series = unwrap_series(&components[0]);
+    assert_eq!(series.count, 3);
+    assert_eq!(series.first, events[0]);
+    assert_eq!(series.overrides.len(), 1);
+    assert_eq!(
+        series.overrides[0].0,
+        create_event(9).start_time.naive_local()
+    );
+    assert_eq!(series.overrides[0].1, &events[1]);
+}
+
+#[test]
+fn first_occurrence_can_be_override() {
+    let mut cancelled = create_event(2);
+    cancelled.status = crate::generate_ics::EventStatus::Cancelled;
+    let events = vec![cancelled, create_event(9), create_event(16)];
+
+    let components = find(&events);
+    let series = unwrap_series(&components[0]);
+    assert_eq!(series.first, create_event(2));
+    assert_eq!(series.overrides.len(), 1);
+    assert_eq!(
+        series.overrides[0].0,
+        create_event(2).start_time.naive_local()
+    );
+}
+
+#[test]
+fn identity_does_not_depend_on_first_week() {
+    let events = vec![create_event(2), create_event(9)];
+    let later_events = vec![create_event(9), create_event(16)];
+    assert_eq!(
+        unwrap_series(&find(&events)[0]).identity,
+        unwrap_series(&find(&later_events)[0]).identity
+    );
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use chrono::TimeDelta;
+use chrono_tz::Tz;
+
+use crate::http_server::Calendars;
+use crate::renames::Renames;
+use crate::userconfig::TimeFormat;
+
+/// Everything the build needs to know about its environment
+pub struct Settings {
+    pub eventfiles_dir: PathBuf,
+    pub userconfig_dir: PathBuf,
+    pub calendars_dir: PathBuf,
+    /// Only report what would change without touching the calendars
+    pub dry_run: bool,
+    /// Zone of the eventfiles which is also the default zone of the calendars
+    pub timezone: Tz,
+    /// Default of how times are written into the calendars
+    pub time_format: TimeFormat,
+    /// Changes are applied to the closest event within this tolerance when no event starts exactly at the change
+    pub change_tolerance: TimeDelta,
+    /// Status of each user like unmatched changes is written to this directory for the Telegram bot
+    pub status_dir: Option<PathBuf>,
+    /// JSON lines describing the changes of every build run are appended to this file
+    pub report_file: Option<PathBuf>,
+    /// Event names which changed and are replaced in userconfigs and eventfiles
+    pub renames: Renames,
+    /// Calendars served by the built-in HTTP server when enabled
+    pub served_calendars: Option<Calendars>,
+}
@@ -0,0 +1,22 @@
+/// 64 bit FNV-1a hash <http://www.isthe.com/chongo/tech/comp/fnv/>
+///
+/// Unlike [`std::hash::DefaultHasher`] the result is the same on every platform and every Rust release.
+/// Use it for everything that leaves the process like UIDs.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+#[test]
+fn fnv1a_reference_values() {
+    assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::apply_changes::Mismatches;
+use crate::output_files::{remove_temporary_files, write_atomically};
+use crate::settings::Settings;
+use crate::userconfig::Change;
+
+/// What the Telegram bot should tell the user about their userconfig
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status<'a> {
+    /// Changes whose event does not exist (anymore). The user should fix or delete them.
+    unmatched_changes: &'a [Change],
+    /// Changes applied to an event which moved a bit. The user should confirm them.
+    fuzzy_matched_changes: &'a [Change],
+}
+
+pub fn ensure_directory(settings: &Settings) -> std::io::Result<()> {
+    let Some(directory) = &settings.status_dir else {
+        return Ok(());
+    };
+    fs::create_dir_all(directory)?;
+    remove_temporary_files(directory)
+}
+
+/// Write the status of the user when it differs from the existing one
+pub fn write(settings: &Settings, chat_id: i64, mismatches: &Mismatches) -> anyhow::Result<()> {
+    let Some(path) = path(settings, chat_id) else {
+        return Ok(());
+    };
+
+    let mut content = serde_json::to_string_pretty(&Status {
+        unmatched_changes: &mismatches.unmatched,
+        fuzzy_matched_changes: &mismatches.fuzzy,
+    })?;
+    content += "\n";
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+    write_atomically(&path, &content)?;
+    Ok(())
+}
+
+pub fn remove(settings: &Settings, chat_id: i64) -> std::io::Result<()> {
+    match path(settings, chat_id) {
+        Some(path) if path.exists() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+/// Remove the status files of users not in `chat_ids` like the ones whose userconfig is gone
+pub fn retain(settings: &Settings, chat_ids: &[i64]) -> std::io::Result<()> {
+    let Some(directory) = &settings.status_dir else {
+        return Ok(());
+    };
+    if settings.dry_run {
+        return Ok(());
+    }
+    for maybe_entry in fs::read_dir(directory)? {
+        let path = maybe_entry?.path();
+        let is_orphaned = path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+            && path
+                .file_stem()
+                .and_then(|stem| stem.to_str()?.parse::<i64>().ok())
+                .is_some_and(|chat_id| !chat_ids.contains(&chat_id));
+        if is_orphaned {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn path(settings: &Settings, chat_id: i64) -> Option<PathBuf> {
+    settings
+        .status_dir
+        .as_ref()
+        .map(|directory| directory.join(format!("{chat_id}.json")))
+}
@@ -0,0 +1,142 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::events;
+use crate::userconfig::UserconfigFile;
+
+struct Subscriber {
+    chat_id: i64,
+    sources: Vec<String>,
+}
+
+/// Index of which userconfig subscribes to which eventfile.
+///
+/// Keeps an eventfile change from rebuilding the calendars of every user.
+#[derive(Default)]
+pub struct Subscriptions {
+    by_source: HashMap<String, BTreeSet<String>>,
+    by_userconfig: HashMap<String, Subscriber>,
+}
+
+impl Subscriptions {
+    pub fn new(all: &[(String, UserconfigFile)]) -> Self {
+        let mut subscriptions = Self::default();
+        for (userconfig_filename, content) in all {
+            subscriptions.update(userconfig_filename, content);
+        }
+        subscriptions
+    }
+
+    pub fn update(&mut self, userconfig_filename: &str, content: &UserconfigFile) {
+        self.remove(userconfig_filename);
+
+        let sources = content
+            .config
+            .events
+            .keys()
+            .map(|name| events::source(name))
+            .collect::<Vec<_>>();
+        for source in &sources {
+            self.by_source
+                .entry(source.clone())
+                .or_default()
+                .insert(userconfig_filename.to_owned());
+        }
+
+        self.by_userconfig.insert(
+            userconfig_filename.to_owned(),
+            Subscriber {
+                chat_id: content.chat.id,
+                sources,
+            },
+        );
+    }
+
+    /// Returns the chat id of the removed userconfig when it was known
+    pub fn remove(&mut self, userconfig_filename: &str) -> Option<i64> {
+        let subscriber = self.by_userconfig.remove(userconfig_filename)?;
+        for source in subscriber.sources {
+            if let Some(userconfigs) = self.by_source.get_mut(&source) {
+                userconfigs.remove(userconfig_filename);
+                if userconfigs.is_empty() {
+                    self.by_source.remove(&source);
+                }
+            }
+        }
+        Some(subscriber.chat_id)
+    }
+
+    /// Userconfig filenames which subscribe to at least one of the given eventfiles
+    pub fn userconfigs_of_eventfiles(&self, eventfile_filenames: &[String]) -> BTreeSet<String> {
+        eventfile_filenames
+            .iter()
+            .filter_map(|filename| filename.strip_suffix(".json"))
+            .filter_map(|source| self.by_source.get(source))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn create_userconfig(chat_id: i64, events: &[&str]) -> UserconfigFile {
+    let events = events
+        .iter()
+        .map(|name| format!(r#""{name}": {{}}"#))
+        .collect::<Vec<_>>()
+        .join(", ");
+    serde_json::from_str(&format!(
+        r#"{{"chat": {{"id": {chat_id}, "first_name": "Peter"}}, "config": {{"calendarfileSuffix": "123qwe", "events": {{{events}}}}}}}"#
+    ))
+    .unwrap()
+}
+
+#[cfg(test)]
+fn create_subscriptions() -> Subscriptions {
+    Subscriptions::new(&[
+        (
+            "1.json".to_owned(),
+            create_userconfig(1, &["BTI1-TI", "BTI5-VSP/01"]),
+        ),
+        ("2.json".to_owned(), create_userconfig(2, &["BTI1-TI"])),
+        ("3.json".to_owned(), create_userconfig(3, &[])),
+    ])
+}
+
+#[test]
+fn eventfile_change_only_affects_subscribers() {
+    let subscriptions = create_subscriptions();
+    let affected = subscriptions.userconfigs_of_eventfiles(&["BTI5-VSP-01.json".to_owned()]);
+    assert_eq!(affected.into_iter().collect::<Vec<_>>(), ["1.json"]);
+
+    let affected = subscriptions
+        .userconfigs_of_eventfiles(&["BTI1-TI.json".to_owned(), "BTI5-VSP-01.json".to_owned()]);
+    assert_eq!(
+        affected.into_iter().collect::<Vec<_>>(),
+        ["1.json", "2.json"]
+    );
+
+    let affected = subscriptions.userconfigs_of_eventfiles(&["BTI5-VS.json".to_owned()]);
+    assert!(affected.is_empty());
+}
+
+#[test]
+fn updated_userconfig_changes_subscriptions() {
+    let mut subscriptions = create_subscriptions();
+    subscriptions.update("2.json", &create_userconfig(2, &["BTI5-VS"]));
+
+    let affected = subscriptions.userconfigs_of_eventfiles(&["BTI1-TI.json".to_owned()]);
+    assert_eq!(affected.into_iter().collect::<Vec<_>>(), ["1.json"]);
+
+    let affected = subscriptions.userconfigs_of_eventfiles(&["BTI5-VS.json".to_owned()]);
+    assert_eq!(affected.into_iter().collect::<Vec<_>>(), ["2.json"]);
+}
+
+#[test]
+fn removed_userconfig_is_no_subscriber() {
+    let mut subscriptions = create_subscriptions();
+    assert_eq!(subscriptions.remove("1.json"), Some(1));
+    assert_eq!(subscriptions.remove("1.json"), None);
+
+    let affected = subscriptions.userconfigs_of_eventfiles(&["BTI5-VSP-01.json".to_owned()]);
+    assert!(affected.is_empty());
+}
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
-use chrono::{NaiveDateTime, NaiveTime, TimeZone as _};
-use chrono_tz::Europe::Berlin;
-use serde::Deserialize;
+use chrono::{DateTime, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
 pub struct UserconfigFile {
@@ -16,22 +16,181 @@ pub struct Chat {
     pub first_name: String,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
-#[serde(rename_all = "camelCase")]
+/// How events removed via a change are shown
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "RawRemovedEvents")]
 pub enum RemovedEvents {
-    #[default]
+    /// Not in the calendar at all
+    Removed,
+    /// Still in the calendar with these marks
+    Marked(RemovedEventMarks),
+}
+
+impl Default for RemovedEvents {
+    fn default() -> Self {
+        Self::Marked(RemovedEventMarks {
+            cancelled: true,
+            ..RemovedEventMarks::default()
+        })
+    }
+}
+
+/// Marks of a removed event which stays in the calendar. They can be combined.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedEventMarks {
+    /// `STATUS:CANCELLED` which most clients show crossed out
+    #[serde(default)]
+    pub cancelled: bool,
+    /// [`crate::messages::removed_marker`] like `🚫` in front of the title
+    #[serde(default)]
+    pub emoji: bool,
+    /// Own text in front of the title like `[ENTFÄLLT]`
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Shown as free time so it does not block free/busy
+    #[serde(default)]
+    pub transparent: bool,
+}
+
+/// Either one of the modes from before the marks could be combined or the marks themselves
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "expected `cancelled`, `removed`, `emoji` or an object with `cancelled`, `emoji`, `prefix` and `transparent`"
+)]
+enum RawRemovedEvents {
+    Mode(RemovedEventsMode),
+    Marks(RemovedEventMarks),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum RemovedEventsMode {
     Cancelled,
     Removed,
     Emoji,
 }
 
-#[derive(Deserialize, Debug)]
+impl TryFrom<RawRemovedEvents> for RemovedEvents {
+    type Error = &'static str;
+
+    fn try_from(raw: RawRemovedEvents) -> Result<Self, Self::Error> {
+        Ok(match raw {
+            RawRemovedEvents::Mode(RemovedEventsMode::Cancelled) => Self::default(),
+            RawRemovedEvents::Mode(RemovedEventsMode::Removed) => Self::Removed,
+            RawRemovedEvents::Mode(RemovedEventsMode::Emoji) => Self::Marked(RemovedEventMarks {
+                emoji: true,
+                ..RemovedEventMarks::default()
+            }),
+            RawRemovedEvents::Marks(RemovedEventMarks {
+                cancelled: false,
+                emoji: false,
+                prefix: None,
+                transparent: false,
+            }) => {
+                return Err(
+                    "removedEvents needs at least one of `cancelled`, `emoji`, `prefix` and `transparent` or else removed events look like any other",
+                );
+            }
+            RawRemovedEvents::Marks(marks) => Self::Marked(marks),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurringEvents {
+    /// Every occurrence is its own VEVENT
+    #[default]
+    Separate,
+    /// Weekly occurrences are combined into a VEVENT with RRULE
+    Series,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum TimeFormat {
+    /// Local times of the zone with a VTIMEZONE like `DTSTART;TZID=Europe/Berlin:20200409T081500`
+    #[default]
+    Local,
+    /// UTC times without a VTIMEZONE like `DTSTART:20200409T061500Z`
+    Utc,
+}
+
+/// Language of the texts the parser writes into the calendar
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "de")]
+    German,
+    #[serde(rename = "en")]
+    English,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AlarmAction {
+    /// Some clients ignore sounds without an attached sound file
+    #[default]
+    Audio,
+    /// Show a message
+    Display,
+}
+
+/// Reminder before the start of an event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub minutes_before: u16,
+    #[serde(default)]
+    pub action: AlarmAction,
+    /// Message of a display alarm. Defaults to the name of the event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct EventDetails {
+    /// Single audio alert. Kept for userconfigs from before `alerts`.
     #[serde(default)]
     pub alert_minutes_before: Option<u16>,
     #[serde(default)]
+    pub alerts: Vec<Alert>,
+    #[serde(default)]
     pub notes: Option<String>,
+    /// Readable name like `Verteilte Systeme Praktikum` for `BTI5-VSP/01`
+    #[serde(default)]
+    pub alias: Option<String>,
+    /// Template of the title like `{alias} ({location})`.
+    /// Supports `{name}`, `{alias}` and `{location}`. Defaults to `{alias}` when there is an alias.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Like `lecture`, `lab` or `tutorial`
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// CSS3 color name like `turquoise` <https://www.rfc-editor.org/rfc/rfc7986#section-5.9>
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Show the event as free time for scheduling like an optional tutorial
+    #[serde(default)]
+    pub transparent: bool,
+}
+
+impl EventDetails {
+    /// The alert of `alertMinutesBefore` followed by the other alerts
+    pub fn alerts(&self) -> Vec<Alert> {
+        self.alert_minutes_before
+            .map(|minutes_before| Alert {
+                minutes_before,
+                action: AlarmAction::Audio,
+                description: None,
+            })
+            .into_iter()
+            .chain(self.alerts.iter().cloned())
+            .collect()
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -46,29 +205,233 @@ pub struct Userconfig {
 
     #[serde(default)]
     pub removed_events: RemovedEvents,
+
+    #[serde(default)]
+    pub recurring_events: RecurringEvents,
+
+    /// IANA zone the calendar is shown in. Defaults to the zone of the eventfiles.
+    #[serde(default)]
+    pub timezone: Option<Tz>,
+
+    /// How times are written into the calendar. Defaults to the global setting.
+    #[serde(default)]
+    pub time_format: Option<TimeFormat>,
+
+    #[serde(default)]
+    pub language: Language,
+
+    /// Old event names the userconfig used before they were replaced via [`crate::renames::Renames`]
+    #[serde(skip)]
+    pub renamed_events: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+/// Change of the user to a single occurrence of an event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "RawChange", into = "RawChange")]
 pub struct Change {
     pub name: String,
+    /// Start of the event the change is meant for
+    pub date: DateTime<Utc>,
+    pub kind: ChangeKind,
+}
 
-    #[serde(deserialize_with = "deserialize_change_date")]
-    pub date: NaiveDateTime,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Event which is not part of an eventfile starting at the date
+    Add(Addition),
+    /// The modification is still visible when removed events stay in the calendar
+    Remove(Modification),
+    Modify(Modification),
+    /// Can not be applied like one which adds and removes at once.
+    /// Still parsed so the rest of the userconfig works and the user can be asked to fix it.
+    Invalid(InvalidChange),
+}
 
-    #[serde(default)]
-    pub add: bool,
-    #[serde(default)]
-    pub remove: bool,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Addition {
+    /// Start on the day of the date. Defaults to the time of the date.
+    pub starttime: Option<NaiveTime>,
+    pub endtime: NaiveTime,
+    /// Shown instead of the name like `Nachklausur`
+    pub title: Option<String>,
+    pub namesuffix: Option<String>,
+    pub room: Option<String>,
+    /// Defaults to a note that the event was added via the Telegram bot
+    pub description: Option<String>,
+    pub alerts: Option<Vec<Alert>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidChange {
+    /// Like `can not add and remove at once`
+    pub reason: &'static str,
+    /// Kept to be written unaltered so the Telegram bot can recognize the change
+    raw: Box<RawChange>,
+}
 
-    #[serde(default, deserialize_with = "deserialize_change_time")]
-    /// Used when adapting events
+/// Adaption of an existing event. Everything not set stays the same.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Modification {
     pub starttime: Option<NaiveTime>,
-    #[serde(default, deserialize_with = "deserialize_change_time")]
-    /// Used for adapting and creating new events
     pub endtime: Option<NaiveTime>,
-
     pub namesuffix: Option<String>,
     pub room: Option<String>,
+    /// Replaces the alerts of this occurrence. An empty list removes them.
+    pub alerts: Option<Vec<Alert>>,
+}
+
+impl Change {
+    /// Why the change can not be applied like `change of Tree at 2020-12-20T22:04 does nothing, …`
+    pub fn problem(&self) -> Option<String> {
+        let ChangeKind::Invalid(invalid) = &self.kind else {
+            return None;
+        };
+        Some(format!(
+            "change of {} at {} {}",
+            self.name,
+            self.date.format(CHANGE_DATE_FORMAT),
+            invalid.reason
+        ))
+    }
+}
+
+impl From<RawChange> for Change {
+    fn from(raw: RawChange) -> Self {
+        let kind = kind_of(&raw).unwrap_or_else(|reason| {
+            ChangeKind::Invalid(InvalidChange {
+                reason,
+                raw: Box::new(raw.clone()),
+            })
+        });
+        Self {
+            name: raw.name,
+            date: raw.date,
+            kind,
+        }
+    }
+}
+
+fn kind_of(raw: &RawChange) -> Result<ChangeKind, &'static str> {
+    if raw.add {
+        if raw.remove {
+            return Err("can not add and remove at once");
+        }
+        return Ok(ChangeKind::Add(Addition {
+            starttime: raw.starttime,
+            endtime: raw.endtime.ok_or("adds an event without endtime")?,
+            title: raw.title.clone(),
+            namesuffix: raw.namesuffix.clone(),
+            room: raw.room.clone(),
+            description: raw.description.clone(),
+            alerts: raw.alerts.clone(),
+        }));
+    }
+    if raw.title.is_some() || raw.description.is_some() {
+        return Err("sets title or description which are only for added events");
+    }
+    let modification = Modification {
+        starttime: raw.starttime,
+        endtime: raw.endtime,
+        namesuffix: raw.namesuffix.clone(),
+        room: raw.room.clone(),
+        alerts: raw.alerts.clone(),
+    };
+    if raw.remove {
+        Ok(ChangeKind::Remove(modification))
+    } else if modification == Modification::default() {
+        Err("does nothing, it should add, remove or modify the event")
+    } else {
+        Ok(ChangeKind::Modify(modification))
+    }
+}
+
+impl From<Change> for RawChange {
+    fn from(change: Change) -> Self {
+        let mut raw = Self {
+            name: change.name,
+            date: change.date,
+            add: false,
+            remove: false,
+            starttime: None,
+            endtime: None,
+            title: None,
+            namesuffix: None,
+            room: None,
+            description: None,
+            alerts: None,
+        };
+        raw.remove = matches!(change.kind, ChangeKind::Remove(_));
+        match change.kind {
+            ChangeKind::Invalid(invalid) => {
+                return Self {
+                    name: raw.name,
+                    date: raw.date,
+                    ..*invalid.raw
+                };
+            }
+            ChangeKind::Add(addition) => {
+                raw.add = true;
+                raw.starttime = addition.starttime;
+                raw.endtime = Some(addition.endtime);
+                raw.title = addition.title;
+                raw.namesuffix = addition.namesuffix;
+                raw.room = addition.room;
+                raw.description = addition.description;
+                raw.alerts = addition.alerts;
+            }
+            ChangeKind::Remove(modification) | ChangeKind::Modify(modification) => {
+                raw.starttime = modification.starttime;
+                raw.endtime = modification.endtime;
+                raw.namesuffix = modification.namesuffix;
+                raw.room = modification.room;
+                raw.alerts = modification.alerts;
+            }
+        }
+        raw
+    }
+}
+
+/// Serialized the same way the Telegram bot writes it so it can recognize the change
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+struct RawChange {
+    name: String,
+
+    #[serde(
+        deserialize_with = "deserialize_change_date",
+        serialize_with = "serialize_change_date"
+    )]
+    date: DateTime<Utc>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    add: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    remove: bool,
+
+    #[serde(
+        default,
+        deserialize_with = "deserialize_change_time",
+        serialize_with = "serialize_change_time",
+        skip_serializing_if = "Option::is_none"
+    )]
+    starttime: Option<NaiveTime>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_change_time",
+        serialize_with = "serialize_change_time",
+        skip_serializing_if = "Option::is_none"
+    )]
+    endtime: Option<NaiveTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namesuffix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    room: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alerts: Option<Vec<Alert>>,
 }
 
 fn deserialize_change_time<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error>
@@ -80,7 +443,18 @@ where
     Ok(Some(time))
 }
 
-fn deserialize_change_date<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
+#[expect(clippy::ref_option, reason = "signature required by serde")]
+fn serialize_change_time<S>(time: &Option<NaiveTime>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match time {
+        Some(time) => serializer.serialize_str(&time.format("%H:%M").to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_change_date<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -88,19 +462,32 @@ where
     parse_change_date(&raw).map_err(serde::de::Error::custom)
 }
 
-fn parse_change_date(raw: &str) -> Result<NaiveDateTime, chrono::ParseError> {
+fn serialize_change_date<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&date.format(CHANGE_DATE_FORMAT).to_string())
+}
+
+/// Change dates are in UTC without seconds like `2020-07-01T06:30`
+pub const CHANGE_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// Also accepts a space instead of the `T` like `2020-07-01 06:30`
+fn parse_change_date(raw: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
     let tless = raw.replace('T', " ");
-    let utc = NaiveDateTime::parse_from_str(&tless, "%Y-%m-%d %H:%M")?;
-    let date_time = Berlin.from_utc_datetime(&utc);
-    let naive = date_time.naive_local();
-    Ok(naive)
+    Ok(NaiveDateTime::parse_from_str(&tless, "%Y-%m-%d %H:%M")?.and_utc())
 }
 
 #[test]
-fn can_parse_change_date_from_utc_to_local() {
+fn can_parse_change_date_as_utc() {
     let actual = parse_change_date("2020-07-01T06:30").unwrap();
-    let string = actual.and_local_timezone(Berlin).unwrap().to_rfc3339();
-    assert_eq!(string, "2020-07-01T08:30:00+02:00");
+    assert_eq!(actual.to_rfc3339(), "2020-07-01T06:30:00+00:00");
+}
+
+#[test]
+fn can_parse_change_date_with_space() {
+    let actual = parse_change_date("2020-07-01 06:30").unwrap();
+    assert_eq!(actual.to_rfc3339(), "2020-07-01T06:30:00+00:00");
 }
 
 #[test]
@@ -132,7 +519,10 @@ fn can_deserialize_minimal_userconfig() -> Result<(), serde_json::Error> {
     assert_eq!(test.calendarfile_suffix, "123qwe");
     assert_eq!(test.changes.len(), 0);
     assert_eq!(test.events.len(), 0);
-    assert_eq!(test.removed_events, RemovedEvents::Cancelled);
+    assert_eq!(test.removed_events, RemovedEvents::default());
+    assert_eq!(test.recurring_events, RecurringEvents::Separate);
+    assert_eq!(test.timezone, None);
+    assert_eq!(test.language, Language::German);
 
     Ok(())
 }
@@ -155,22 +545,159 @@ fn can_deserialize_userconfig_with_event_map() -> Result<(), serde_json::Error>
 }
 
 #[test]
-fn can_deserialize_minimal_change() -> Result<(), serde_json::Error> {
-    let test: Change = serde_json::from_str(r#"{"name": "Tree", "date": "2020-12-20T22:04"}"#)?;
+fn can_deserialize_removed_events_mode() -> Result<(), serde_json::Error> {
+    let test: RemovedEvents = serde_json::from_str(r#""emoji""#)?;
+    assert_eq!(
+        test,
+        RemovedEvents::Marked(RemovedEventMarks {
+            emoji: true,
+            ..RemovedEventMarks::default()
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_combined_removed_event_marks() -> Result<(), serde_json::Error> {
+    let test: RemovedEvents = serde_json::from_str(
+        r#"{"cancelled": true, "emoji": true, "prefix": "[ENTFÄLLT]", "transparent": true}"#,
+    )?;
+    assert_eq!(
+        test,
+        RemovedEvents::Marked(RemovedEventMarks {
+            cancelled: true,
+            emoji: true,
+            prefix: Some("[ENTFÄLLT]".to_owned()),
+            transparent: true,
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn unknown_removed_events_mode_fails() {
+    let error = serde_json::from_str::<RemovedEvents>(r#""hidden""#).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected `cancelled`, `removed`, `emoji` or an object with `cancelled`, `emoji`, `prefix` and `transparent`"
+    );
+}
+
+#[test]
+fn removed_event_marks_without_mark_fail() {
+    for json in ["{}", r#"{"cancelled": false, "emoji": false}"#] {
+        let error = serde_json::from_str::<RemovedEvents>(json).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "removedEvents needs at least one of `cancelled`, `emoji`, `prefix` and `transparent` or else removed events look like any other"
+        );
+    }
+}
+
+#[test]
+fn can_deserialize_userconfig_with_recurring_series() -> Result<(), serde_json::Error> {
+    let test: Userconfig = serde_json::from_str(
+        r#"{"calendarfileSuffix": "123qwe", "events": {}, "recurringEvents": "series"}"#,
+    )?;
+    assert_eq!(test.recurring_events, RecurringEvents::Series);
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_userconfig_with_timezone() -> Result<(), serde_json::Error> {
+    let test: Userconfig = serde_json::from_str(
+        r#"{"calendarfileSuffix": "123qwe", "events": {}, "timezone": "America/New_York"}"#,
+    )?;
+    assert_eq!(test.timezone, Some(chrono_tz::America::New_York));
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_event_details_with_alerts() -> Result<(), serde_json::Error> {
+    let test: EventDetails = serde_json::from_str(
+        r#"{"alertMinutesBefore": 15, "alerts": [{"minutesBefore": 1440, "action": "display", "description": "Klausur"}, {"minutesBefore": 60}]}"#,
+    )?;
+    let alerts = test.alerts();
+    assert_eq!(alerts.len(), 3);
+    assert_eq!(alerts[0].minutes_before, 15);
+    assert_eq!(alerts[0].action, AlarmAction::Audio);
+    assert_eq!(alerts[1].action, AlarmAction::Display);
+    assert_eq!(alerts[1].description.as_deref(), Some("Klausur"));
+    assert_eq!(alerts[2].action, AlarmAction::Audio);
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_event_details_with_appearance() -> Result<(), serde_json::Error> {
+    let test: EventDetails = serde_json::from_str(
+        r#"{"categories": ["lab"], "color": "turquoise", "transparent": true}"#,
+    )?;
+    assert_eq!(test.categories, ["lab"]);
+    assert_eq!(test.color.as_deref(), Some("turquoise"));
+    assert!(test.transparent);
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_change_with_alerts() -> Result<(), serde_json::Error> {
+    let test: Change =
+        serde_json::from_str(r#"{"name": "Tree", "date": "2020-12-20T22:04", "alerts": []}"#)?;
+    assert_eq!(
+        test.kind,
+        ChangeKind::Modify(Modification {
+            alerts: Some(Vec::new()),
+            ..Modification::default()
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_userconfig_with_time_format() -> Result<(), serde_json::Error> {
+    let test: Userconfig = serde_json::from_str(
+        r#"{"calendarfileSuffix": "123qwe", "events": {}, "timeFormat": "utc"}"#,
+    )?;
+    assert_eq!(test.time_format, Some(TimeFormat::Utc));
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_userconfig_with_language() -> Result<(), serde_json::Error> {
+    let test: Userconfig = serde_json::from_str(
+        r#"{"calendarfileSuffix": "123qwe", "events": {}, "language": "en"}"#,
+    )?;
+    assert_eq!(test.language, Language::English);
+    Ok(())
+}
+
+#[test]
+fn userconfig_with_unknown_timezone_fails() {
+    let test: Result<Userconfig, serde_json::Error> = serde_json::from_str(
+        r#"{"calendarfileSuffix": "123qwe", "events": {}, "timezone": "Mars/Olympus_Mons"}"#,
+    );
+    assert!(test.is_err());
+}
+
+#[test]
+fn can_deserialize_change_modify() -> Result<(), serde_json::Error> {
+    let test: Change =
+        serde_json::from_str(r#"{"name": "Tree", "date": "2020-12-20T22:04", "room": "1060"}"#)?;
     assert_eq!(test.name, "Tree");
     assert_eq!(
         test.date,
         chrono::NaiveDate::from_ymd_opt(2020, 12, 20)
             .unwrap()
-            .and_hms_opt(23, 4, 0)
+            .and_hms_opt(22, 4, 0)
             .unwrap()
+            .and_utc()
+    );
+    assert_eq!(
+        test.kind,
+        ChangeKind::Modify(Modification {
+            room: Some("1060".to_owned()),
+            ..Modification::default()
+        })
     );
-    assert!(!test.add);
-    assert!(!test.remove);
-    assert_eq!(test.starttime, None);
-    assert_eq!(test.endtime, None);
-    assert_eq!(test.namesuffix, None);
-    assert_eq!(test.room, None);
     Ok(())
 }
 
@@ -183,15 +710,11 @@ fn can_deserialize_change_remove() -> Result<(), serde_json::Error> {
         test.date,
         chrono::NaiveDate::from_ymd_opt(2020, 12, 20)
             .unwrap()
-            .and_hms_opt(23, 4, 0)
+            .and_hms_opt(22, 4, 0)
             .unwrap()
+            .and_utc()
     );
-    assert!(!test.add);
-    assert!(test.remove);
-    assert_eq!(test.starttime, None);
-    assert_eq!(test.endtime, None);
-    assert_eq!(test.namesuffix, None);
-    assert_eq!(test.room, None);
+    assert_eq!(test.kind, ChangeKind::Remove(Modification::default()));
     Ok(())
 }
 
@@ -205,17 +728,102 @@ fn can_deserialize_change_add() -> Result<(), serde_json::Error> {
         test.date,
         chrono::NaiveDate::from_ymd_opt(2020, 12, 20)
             .unwrap()
-            .and_hms_opt(23, 4, 0)
+            .and_hms_opt(22, 4, 0)
+            .unwrap()
+            .and_utc()
+    );
+    assert_eq!(
+        test.kind,
+        ChangeKind::Add(Addition {
+            starttime: None,
+            endtime: NaiveTime::from_hms_opt(23, 42, 0).unwrap(),
+            title: None,
+            namesuffix: None,
+            room: None,
+            description: None,
+            alerts: None,
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_change_add_with_details() -> Result<(), serde_json::Error> {
+    let test: Change = serde_json::from_str(
+        r#"{"name": "BTI5-VS", "date": "2020-12-20T00:00", "add": true, "starttime": "10:00", "endtime": "12:00", "title": "Nachklausur", "description": "Bring a pen", "alerts": [{"minutesBefore": 60}]}"#,
+    )?;
+    let ChangeKind::Add(addition) = test.kind else {
+        panic!("should be an addition: {test:?}");
+    };
+    assert_eq!(addition.starttime, NaiveTime::from_hms_opt(10, 0, 0));
+    assert_eq!(addition.title.as_deref(), Some("Nachklausur"));
+    assert_eq!(addition.description.as_deref(), Some("Bring a pen"));
+    assert_eq!(addition.alerts.map(|alerts| alerts.len()), Some(1));
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_minimal_change() -> Result<(), serde_json::Error> {
+    let test: Change = serde_json::from_str(r#"{"name": "Tree", "date": "2020-12-20T22:04"}"#)?;
+    assert_eq!(test.name, "Tree");
+    assert_eq!(
+        test.date,
+        chrono::NaiveDate::from_ymd_opt(2020, 12, 20)
+            .unwrap()
+            .and_hms_opt(22, 4, 0)
             .unwrap()
+            .and_utc()
+    );
+    assert!(matches!(test.kind, ChangeKind::Invalid(_)));
+    Ok(())
+}
+
+#[cfg(test)]
+fn change_problem(raw: &str) -> Option<String> {
+    serde_json::from_str::<Change>(raw).unwrap().problem()
+}
+
+#[test]
+fn contradictory_changes_are_invalid() {
+    assert_eq!(
+        change_problem(
+            r#"{"name": "Tree", "date": "2020-12-20T22:04", "add": true, "remove": true}"#
+        )
+        .unwrap(),
+        "change of Tree at 2020-12-20T22:04 can not add and remove at once"
+    );
+    assert_eq!(
+        change_problem(r#"{"name": "Tree", "date": "2020-12-20T22:04", "add": true}"#).unwrap(),
+        "change of Tree at 2020-12-20T22:04 adds an event without endtime"
+    );
+    assert_eq!(
+        change_problem(
+            r#"{"name": "Tree", "date": "2020-12-20T22:04", "remove": true, "description": "Moved online"}"#
+        )
+        .unwrap(),
+        "change of Tree at 2020-12-20T22:04 sets title or description which are only for added events"
+    );
+    assert_eq!(
+        change_problem(r#"{"name": "Tree", "date": "2020-12-20T22:04"}"#).unwrap(),
+        "change of Tree at 2020-12-20T22:04 does nothing, it should add, remove or modify the event"
     );
-    assert!(test.add);
-    assert!(!test.remove);
-    assert_eq!(test.starttime, None);
     assert_eq!(
-        test.endtime,
-        Some(NaiveTime::from_hms_opt(23, 42, 0).unwrap())
+        change_problem(r#"{"name": "Tree", "date": "2020-12-20T22:04", "remove": true}"#),
+        None
     );
-    assert_eq!(test.namesuffix, None);
-    assert_eq!(test.room, None);
+}
+
+#[test]
+fn change_serializes_like_the_telegram_bot() -> Result<(), serde_json::Error> {
+    for raw in [
+        r#"{"name":"Tree","date":"2020-12-20T22:04","remove":true,"starttime":"08:15","room":"1060"}"#,
+        r#"{"name":"Tree","date":"2020-12-20T22:04","add":true,"starttime":"08:15","endtime":"23:42","title":"Party","room":"1060","description":"Bring cake"}"#,
+        r#"{"name":"Tree","date":"2020-12-20T22:04","alerts":[{"minutesBefore":15,"action":"audio"}]}"#,
+        r#"{"name":"Tree","date":"2020-12-20T22:04","add":true,"remove":true,"room":"1060"}"#,
+        r#"{"name":"Tree","date":"2020-12-20T22:04"}"#,
+    ] {
+        let test: Change = serde_json::from_str(raw)?;
+        assert_eq!(serde_json::to_string(&test)?, raw);
+    }
     Ok(())
 }
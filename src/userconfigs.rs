@@ -1,37 +1,59 @@
 use std::fs;
-use std::path::Path;
 
 use anyhow::Context as _;
 
+use crate::changestatus::Changestatus;
+use crate::settings::Settings;
 use crate::userconfig::UserconfigFile;
 
-pub const FOLDER: &str = "userconfig";
-
-pub fn load_specific(filename: &str) -> anyhow::Result<UserconfigFile> {
-    let path = Path::new(FOLDER).join(filename);
+pub fn load_specific(settings: &Settings, filename: &str) -> anyhow::Result<UserconfigFile> {
+    let path = settings.userconfig_dir.join(filename);
     let content = fs::read_to_string(path).context("failed to read")?;
-    let parsed: UserconfigFile = serde_json::from_str(&content).context("failed to parse")?;
+    let mut parsed: UserconfigFile = serde_json::from_str(&content).context("failed to parse")?;
+    parsed.config.renamed_events = settings.renames.apply(&mut parsed.config);
+    if !parsed.config.renamed_events.is_empty() {
+        println!(
+            "userconfig {filename:>16} uses renamed events {:?}",
+            parsed.config.renamed_events
+        );
+    }
     Ok(parsed)
 }
 
-pub fn load_all() -> Vec<UserconfigFile> {
-    let mut successful: Vec<UserconfigFile> = Vec::new();
+/// Chat id of a userconfig named `<chatid>.json`. Only used when the content can not be parsed.
+pub fn chat_id_of_filename(filename: &str) -> Option<i64> {
+    filename.strip_suffix(".json")?.parse().ok()
+}
+
+pub fn exists(settings: &Settings, filename: &str) -> bool {
+    settings.userconfig_dir.join(filename).exists()
+}
+
+/// Successfully loaded userconfigs with their filename and the ones which failed to load
+pub fn load_all(settings: &Settings) -> (Vec<(String, UserconfigFile)>, Vec<Changestatus>) {
+    let mut successful: Vec<(String, UserconfigFile)> = Vec::new();
+    let mut failed: Vec<Changestatus> = Vec::new();
 
-    let existing_files = get_existing_files().expect("should be able to read userconfig directory");
+    let existing_files =
+        get_existing_files(settings).expect("should be able to read userconfig directory");
 
     for filename in existing_files {
-        match load_specific(&filename) {
-            Ok(content) => successful.push(content),
-            Err(err) => println!("skip userconfig {filename:>16}: {err:#}"),
+        match load_specific(settings, &filename) {
+            Ok(content) => successful.push((filename, content)),
+            Err(err) => {
+                println!("skip userconfig {filename:>16}: {err:#}");
+                let chat_id = chat_id_of_filename(&filename);
+                failed.push(Changestatus::failed(filename, chat_id, &err));
+            }
         }
     }
 
-    successful
+    (successful, failed)
 }
 
-fn get_existing_files() -> std::io::Result<Vec<String>> {
+fn get_existing_files(settings: &Settings) -> std::io::Result<Vec<String>> {
     let mut list: Vec<String> = Vec::new();
-    for maybe_entry in fs::read_dir(FOLDER)? {
+    for maybe_entry in fs::read_dir(&settings.userconfig_dir)? {
         let filename = maybe_entry?
             .file_name()
             .into_string()
@@ -45,3 +67,11 @@ fn get_existing_files() -> std::io::Result<Vec<String>> {
 
     Ok(list)
 }
+
+#[test]
+fn chat_id_of_filename_works() {
+    assert_eq!(chat_id_of_filename("42.json"), Some(42));
+    assert_eq!(chat_id_of_filename("-42.json"), Some(-42));
+    assert_eq!(chat_id_of_filename("42.txt"), None);
+    assert_eq!(chat_id_of_filename("foo.json"), None);
+}
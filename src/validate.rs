@@ -0,0 +1,349 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use chrono_tz::Tz;
+use serde::de::DeserializeOwned;
+
+use crate::events::{self, EventEntry};
+use crate::local_time;
+use crate::renames::Renames;
+use crate::settings::Settings;
+use crate::userconfig::{CHANGE_DATE_FORMAT, ChangeKind, UserconfigFile};
+
+/// Check every userconfig and eventfile and print a line per problem or warning.
+/// Fails when there is a problem.
+pub fn validate(settings: &Settings) -> ExitCode {
+    let mut problems = 0;
+    let mut warning_count = 0;
+    let mut files = 0;
+
+    let mut list = |dir: &Path| {
+        json_files(dir).unwrap_or_else(|err| {
+            println!("{}: failed to read directory: {err}", dir.display());
+            problems += 1;
+            Vec::new()
+        })
+    };
+    let userconfigs = list(&settings.userconfig_dir);
+    let eventfiles = list(&settings.eventfiles_dir);
+
+    let has_eventfile = |name: &str| {
+        settings
+            .eventfiles_dir
+            .join(events::source(name) + ".json")
+            .exists()
+    };
+    for path in userconfigs {
+        files += 1;
+        let mut warnings = Vec::new();
+        let found = parse(&path).map_or_else(
+            |problem| vec![problem],
+            |content: UserconfigFile| {
+                check_userconfig(
+                    &content,
+                    settings.timezone,
+                    &settings.renames,
+                    has_eventfile,
+                    &mut warnings,
+                )
+                .into_iter()
+                .map(Problem::new)
+                .collect()
+            },
+        );
+        problems += report(&path, &found, &warnings);
+        warning_count += warnings.len();
+    }
+
+    for path in eventfiles {
+        files += 1;
+        let mut warnings = Vec::new();
+        let found = parse(&path).map_or_else(
+            |problem| vec![problem],
+            |entries: Vec<EventEntry>| {
+                check_eventfile(&entries, settings.timezone, &mut warnings)
+                    .into_iter()
+                    .map(Problem::new)
+                    .collect()
+            },
+        );
+        problems += report(&path, &found, &warnings);
+        warning_count += warnings.len();
+    }
+
+    println!("{problems} problems and {warning_count} warnings in {files} files");
+    if problems == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Sorted JSON files of the directory
+fn json_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Problem of a file with its line and column when known
+#[derive(Debug, PartialEq, Eq)]
+struct Problem {
+    position: Option<(usize, usize)>,
+    message: String,
+}
+
+impl Problem {
+    const fn new(message: String) -> Self {
+        Self {
+            position: None,
+            message,
+        }
+    }
+}
+
+/// Print a line like `userconfig/42.json:3:14: message` per problem
+/// and one like `userconfig/42.json: warning: message` per warning
+fn report(path: &Path, problems: &[Problem], warnings: &[String]) -> usize {
+    let path = path.display();
+    for problem in problems {
+        let message = &problem.message;
+        if let Some((line, column)) = problem.position {
+            println!("{path}:{line}:{column}: {message}");
+        } else {
+            println!("{path}: {message}");
+        }
+    }
+    for warning in warnings {
+        println!("{path}: warning: {warning}");
+    }
+    problems.len()
+}
+
+fn parse<T: DeserializeOwned>(path: &Path) -> Result<T, Problem> {
+    let content =
+        fs::read_to_string(path).map_err(|err| Problem::new(format!("failed to read: {err}")))?;
+    parse_str(&content)
+}
+
+fn parse_str<T: DeserializeOwned>(content: &str) -> Result<T, Problem> {
+    serde_json::from_str(content).map_err(|err| {
+        let message = err.to_string();
+        let position = format!(" at line {} column {}", err.line(), err.column());
+        Problem {
+            position: Some((err.line(), err.column())),
+            message: message
+                .strip_suffix(&position)
+                .unwrap_or(&message)
+                .to_owned(),
+        }
+    })
+}
+
+/// Problems of the userconfig.
+/// Local times of changes which are ambiguous or do not exist are added to the `warnings`.
+fn check_userconfig(
+    content: &UserconfigFile,
+    timezone: Tz,
+    renames: &Renames,
+    has_eventfile: impl Fn(&str) -> bool,
+    warnings: &mut Vec<String>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (i, change) in content.config.changes.iter().enumerate() {
+        let label = format!(
+            "change {i} ({} {})",
+            change.name,
+            change.date.format(CHANGE_DATE_FORMAT)
+        );
+        let date = change.date.with_timezone(&timezone);
+        // Times given in the change as local times of its day
+        let (given_starttime, endtime) = match &change.kind {
+            ChangeKind::Add(addition) => (addition.starttime, Some(addition.endtime)),
+            ChangeKind::Remove(modification) | ChangeKind::Modify(modification) => {
+                (modification.starttime, modification.endtime)
+            }
+            ChangeKind::Invalid(invalid) => {
+                problems.push(format!("{label}: {}", invalid.reason));
+                continue;
+            }
+        };
+        for time in [given_starttime, endtime].into_iter().flatten() {
+            let local = date.date_naive().and_time(time);
+            if let (_, Some(warning)) = local_time::resolve(timezone, local) {
+                warnings.push(format!("{label}: {warning}"));
+            }
+        }
+        let starttime = if matches!(change.kind, ChangeKind::Add(_)) {
+            given_starttime.or_else(|| Some(date.time()))
+        } else {
+            given_starttime
+        };
+        if let Some(starttime) = starttime
+            && let Some(endtime) = endtime
+            && endtime < starttime
+        {
+            problems.push(format!(
+                "{label}: endtime {endtime} is before starttime {starttime}"
+            ));
+        }
+    }
+
+    let mut names = content.config.events.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        if !has_eventfile(renames.resolve(name)) {
+            problems.push(format!("event {name} has no eventfile"));
+        }
+    }
+
+    problems
+}
+
+/// Problems of the eventfile.
+/// Local times which are ambiguous or do not exist in the `timezone` are added to the `warnings`.
+fn check_eventfile(
+    entries: &[EventEntry],
+    timezone: Tz,
+    warnings: &mut Vec<String>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let label = format!("event {i} ({} {})", entry.name, entry.start_time);
+        for time in [entry.start_time, entry.end_time] {
+            if let (_, Some(warning)) = local_time::resolve(timezone, time) {
+                warnings.push(format!("{label}: {warning}"));
+            }
+        }
+        if entry.end_time < entry.start_time {
+            problems.push(format!("{label}: ends before it starts"));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+fn check_userconfig_str(content: &str) -> (Vec<String>, Vec<String>) {
+    let content = parse_str(content).unwrap();
+    let mut warnings = Vec::new();
+    let problems = check_userconfig(
+        &content,
+        chrono_tz::Europe::Berlin,
+        &Renames::default(),
+        |name| name == "BTI5-VS",
+        &mut warnings,
+    );
+    (problems, warnings)
+}
+
+#[test]
+fn syntax_error_has_position() {
+    let result = parse_str::<UserconfigFile>("{\n  \"chat\": 42,\n}");
+    assert_eq!(
+        result.unwrap_err(),
+        Problem {
+            position: Some((2, 12)),
+            message: "invalid type: integer `42`, expected struct Chat".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn contradictory_change_is_a_problem() {
+    let (problems, warnings) = check_userconfig_str(
+        r#"{"chat": {"id": 42, "first_name": "Peter"}, "config": {"calendarfileSuffix": "abc", "events": {}, "changes": [
+            {"name": "BTI5-VS", "date": "2020-04-09T06:15", "add": true, "remove": true}
+        ]}}"#,
+    );
+    assert_eq!(
+        problems,
+        ["change 0 (BTI5-VS 2020-04-09T06:15): can not add and remove at once"]
+    );
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn valid_userconfig() {
+    let (problems, warnings) = check_userconfig_str(
+        r#"{"chat": {"id": 42, "first_name": "Peter"}, "config": {"calendarfileSuffix": "abc", "events": {"BTI5-VS": {}}, "changes": [
+            {"name": "BTI5-VS", "date": "2020-04-09T06:15", "starttime": "08:30", "endtime": "11:30"},
+            {"name": "Extra", "date": "2020-04-10T06:15", "add": true, "endtime": "09:00"}
+        ]}}"#,
+    );
+    assert!(problems.is_empty(), "{problems:?}");
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn semantic_problems_of_userconfig() {
+    let (problems, warnings) = check_userconfig_str(
+        r#"{"chat": {"id": 42, "first_name": "Peter"}, "config": {"calendarfileSuffix": "abc", "events": {"BTI5-VS": {}, "BTI1-TI": {}}, "changes": [
+            {"name": "BTI5-VS", "date": "2020-04-09T06:15", "starttime": "10:00", "endtime": "09:00"},
+            {"name": "Extra", "date": "2020-04-10T06:15", "add": true, "endtime": "08:00"}
+        ]}}"#,
+    );
+    assert_eq!(
+        problems,
+        [
+            "change 0 (BTI5-VS 2020-04-09T06:15): endtime 09:00:00 is before starttime 10:00:00",
+            "change 1 (Extra 2020-04-10T06:15): endtime 08:00:00 is before starttime 08:15:00",
+            "event BTI1-TI has no eventfile",
+        ]
+    );
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn change_time_skipped_by_dst_is_a_warning() {
+    let (problems, warnings) = check_userconfig_str(
+        r#"{"chat": {"id": 42, "first_name": "Peter"}, "config": {"calendarfileSuffix": "abc", "events": {}, "changes": [
+            {"name": "BTI5-VS", "date": "2020-03-29T00:15", "starttime": "02:30", "endtime": "04:00"}
+        ]}}"#,
+    );
+    assert!(problems.is_empty(), "{problems:?}");
+    assert_eq!(
+        warnings,
+        [
+            "change 0 (BTI5-VS 2020-03-29T00:15): 2020-03-29 02:30 does not exist in Europe/Berlin, 2020-03-29 03:30 is used"
+        ]
+    );
+}
+
+#[test]
+fn eventfile_entry_ending_before_start() {
+    let entries: Vec<EventEntry> = parse_str(
+        r#"[{"Name": "BTI1-TI", "Location": "", "Description": "", "StartTime": "2022-01-13T11:40:00", "EndTime": "2022-01-13T11:00:00"}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        check_eventfile(&entries, chrono_tz::Europe::Berlin, &mut Vec::new()),
+        ["event 0 (BTI1-TI 2022-01-13 11:40:00): ends before it starts"]
+    );
+}
+
+#[test]
+fn eventfile_entry_at_ambiguous_time_is_a_warning() {
+    let entries: Vec<EventEntry> = parse_str(
+        r#"[{"Name": "BTI1-TI", "Location": "", "Description": "", "StartTime": "2020-10-25T02:30:00", "EndTime": "2020-10-25T04:00:00"}]"#,
+    )
+    .unwrap();
+    let mut warnings = Vec::new();
+    assert!(check_eventfile(&entries, chrono_tz::Europe::Berlin, &mut warnings).is_empty());
+    assert_eq!(
+        warnings,
+        [
+            "event 0 (BTI1-TI 2020-10-25 02:30:00): 2020-10-25 02:30 occurs twice in Europe/Berlin, the first one is used"
+        ]
+    );
+}
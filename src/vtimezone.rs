@@ -0,0 +1,293 @@
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Offset as _, TimeDelta, TimeZone as _, Utc};
+use chrono_tz::{OffsetComponents as _, OffsetName as _, Tz};
+
+use crate::ics_writer::escape_text;
+
+/// Offset and name of the zone in effect at some time
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Observance {
+    offset: TimeDelta,
+    daylight: bool,
+    name: Option<String>,
+}
+
+/// VTIMEZONE with every transition of the zone between `start` and `end`
+/// <https://www.rfc-editor.org/rfc/rfc5545#section-3.6.5>
+///
+/// The first observance starts at `start` so the whole range is covered even without transitions.
+pub fn write_vtimezone(
+    output: &mut String,
+    timezone: Tz,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) {
+    _ = writeln!(output, "BEGIN:VTIMEZONE\nTZID:{}", timezone.name());
+
+    let mut previous = observance(timezone, start);
+    write_observance(output, &previous, &previous, start);
+    for (at, current) in transitions(timezone, start, end) {
+        write_observance(output, &previous, &current, at);
+        previous = current;
+    }
+
+    *output += "END:VTIMEZONE\n";
+}
+
+fn observance(timezone: Tz, at: DateTime<Utc>) -> Observance {
+    let offset = timezone.offset_from_utc_datetime(&at.naive_utc());
+    Observance {
+        offset: TimeDelta::seconds(offset.fix().local_minus_utc().into()),
+        daylight: !offset.dst_offset().is_zero(),
+        name: offset.abbreviation().map(ToOwned::to_owned),
+    }
+}
+
+/// Instants at which the observance changes.
+/// Found in hourly steps and then narrowed down to the second.
+fn transitions(
+    timezone: Tz,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, Observance)> {
+    let mut result = Vec::new();
+    let mut previous = observance(timezone, start);
+    let mut before = start;
+    while before < end {
+        let after = (before + TimeDelta::hours(1)).min(end);
+        let current = observance(timezone, after);
+        if current != previous {
+            let (mut low, mut high) = (before, after);
+            while (high - low).num_seconds() > 1 {
+                let middle = low + TimeDelta::seconds((high - low).num_seconds() / 2);
+                if observance(timezone, middle) == previous {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            result.push((high, current.clone()));
+            previous = current;
+        }
+        before = after;
+    }
+    result
+}
+
+/// The start of an observance is given in the local time of the previous one
+fn write_observance(output: &mut String, from: &Observance, to: &Observance, at: DateTime<Utc>) {
+    let component = if to.daylight { "DAYLIGHT" } else { "STANDARD" };
+    _ = writeln!(output, "BEGIN:{component}");
+    _ = writeln!(
+        output,
+        "DTSTART:{}",
+        (at.naive_utc() + from.offset).format("%Y%m%dT%H%M%S")
+    );
+    _ = writeln!(output, "TZOFFSETFROM:{}", format_offset(from.offset));
+    _ = writeln!(output, "TZOFFSETTO:{}", format_offset(to.offset));
+    if let Some(name) = &to.name {
+        _ = writeln!(output, "TZNAME:{}", escape_text(name));
+    }
+    _ = writeln!(output, "END:{component}");
+}
+
+/// UTC-OFFSET like `+0100` <https://www.rfc-editor.org/rfc/rfc5545#section-3.3.14>
+fn format_offset(offset: TimeDelta) -> String {
+    let sign = if offset < TimeDelta::zero() { '-' } else { '+' };
+    let seconds = offset.num_seconds().abs();
+    let hours = seconds / 3600;
+    let minutes = seconds / 60 % 60;
+    let seconds = seconds % 60;
+    if seconds == 0 {
+        format!("{sign}{hours:02}{minutes:02}")
+    } else {
+        format!("{sign}{hours:02}{minutes:02}{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+const fn utc(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+    chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc()
+}
+
+#[test]
+fn berlin_2020() {
+    let mut output = String::new();
+    write_vtimezone(
+        &mut output,
+        chrono_tz::Europe::Berlin,
+        utc(2020, 1, 1),
+        utc(2021, 1, 1),
+    );
+    assert_eq!(
+        output,
+        "BEGIN:VTIMEZONE
+TZID:Europe/Berlin
+BEGIN:STANDARD
+DTSTART:20200101T010000
+TZOFFSETFROM:+0100
+TZOFFSETTO:+0100
+TZNAME:CET
+END:STANDARD
+BEGIN:DAYLIGHT
+DTSTART:20200329T020000
+TZOFFSETFROM:+0100
+TZOFFSETTO:+0200
+TZNAME:CEST
+END:DAYLIGHT
+BEGIN:STANDARD
+DTSTART:20201025T030000
+TZOFFSETFROM:+0200
+TZOFFSETTO:+0100
+TZNAME:CET
+END:STANDARD
+END:VTIMEZONE
+"
+    );
+}
+
+#[test]
+fn format_offset_examples() {
+    assert_eq!(format_offset(TimeDelta::hours(1)), "+0100");
+    assert_eq!(format_offset(TimeDelta::minutes(-210)), "-0330");
+    assert_eq!(format_offset(TimeDelta::zero()), "+0000");
+    assert_eq!(format_offset(TimeDelta::seconds(3208)), "+005328");
+}
+
+#[cfg(test)]
+fn utc_transitions(timezone: Tz, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<String> {
+    transitions(timezone, start, end)
+        .into_iter()
+        .map(|(at, observance)| {
+            let mut line = format!(
+                "{} {}",
+                at.format("%Y-%m-%dT%H:%M:%SZ"),
+                format_offset(observance.offset)
+            );
+            if let Some(name) = observance.name {
+                line += " ";
+                line += &name;
+            }
+            line
+        })
+        .collect()
+}
+
+#[test]
+fn berlin_transitions_2020() {
+    assert_eq!(
+        utc_transitions(chrono_tz::Europe::Berlin, utc(2020, 1, 1), utc(2021, 1, 1)),
+        [
+            "2020-03-29T01:00:00Z +0200 CEST",
+            "2020-10-25T01:00:00Z +0100 CET",
+        ]
+    );
+}
+
+#[test]
+fn southern_hemisphere_transitions() {
+    assert_eq!(
+        utc_transitions(
+            chrono_tz::Australia::Sydney,
+            utc(2020, 1, 1),
+            utc(2021, 1, 1)
+        ),
+        [
+            "2020-04-04T16:00:00Z +1000 AEST",
+            "2020-10-03T16:00:00Z +1100 AEDT",
+        ]
+    );
+}
+
+/// Numeric zone names like `+11` have no abbreviation in the tz data
+#[test]
+fn half_hour_daylight_saving() {
+    assert_eq!(
+        utc_transitions(
+            chrono_tz::Australia::Lord_Howe,
+            utc(2020, 7, 1),
+            utc(2021, 1, 1)
+        ),
+        ["2020-10-03T15:30:00Z +1100"]
+    );
+}
+
+#[test]
+fn zone_without_daylight_saving_has_no_transitions() {
+    assert!(utc_transitions(chrono_tz::Asia::Tokyo, utc(2020, 1, 1), utc(2021, 1, 1)).is_empty());
+}
+
+#[test]
+fn permanent_change_of_standard_time() {
+    assert_eq!(
+        utc_transitions(chrono_tz::Europe::Moscow, utc(2014, 1, 1), utc(2015, 1, 1)),
+        ["2014-10-25T22:00:00Z +0300 MSK"]
+    );
+}
+
+#[test]
+fn skipped_day() {
+    assert_eq!(
+        utc_transitions(chrono_tz::Pacific::Apia, utc(2011, 12, 1), utc(2012, 1, 1)),
+        ["2011-12-30T10:00:00Z +1400"]
+    );
+}
+
+#[test]
+fn transitions_outside_of_range_are_ignored() {
+    let mut output = String::new();
+    write_vtimezone(
+        &mut output,
+        chrono_tz::Europe::Berlin,
+        utc(2020, 4, 1),
+        utc(2020, 7, 1),
+    );
+    assert_eq!(
+        output,
+        "BEGIN:VTIMEZONE
+TZID:Europe/Berlin
+BEGIN:DAYLIGHT
+DTSTART:20200401T020000
+TZOFFSETFROM:+0200
+TZOFFSETTO:+0200
+TZNAME:CEST
+END:DAYLIGHT
+END:VTIMEZONE
+"
+    );
+}
+
+#[test]
+fn offset_with_seconds() {
+    let mut output = String::new();
+    write_vtimezone(
+        &mut output,
+        chrono_tz::Africa::Monrovia,
+        utc(1972, 1, 1),
+        utc(1972, 2, 1),
+    );
+    assert_eq!(
+        output,
+        "BEGIN:VTIMEZONE
+TZID:Africa/Monrovia
+BEGIN:STANDARD
+DTSTART:19711231T231530
+TZOFFSETFROM:-004430
+TZOFFSETTO:-004430
+TZNAME:MMT
+END:STANDARD
+BEGIN:STANDARD
+DTSTART:19720107T000000
+TZOFFSETFROM:-004430
+TZOFFSETTO:+0000
+TZNAME:GMT
+END:STANDARD
+END:VTIMEZONE
+"
+    );
+}
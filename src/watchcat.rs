@@ -14,7 +14,7 @@ pub struct Watchcat {
 }
 
 impl Watchcat {
-    pub fn new(folder: &str) -> Self {
+    pub fn new(folder: &Path) -> Self {
         let (tx, rx) = channel();
 
         let mut watcher = new_debouncer(
@@ -25,7 +25,10 @@ impl Watchcat {
                 let mut paths = events
                     .into_iter()
                     .filter(|event| {
-                        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                        matches!(
+                            event.kind,
+                            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                        )
                     })
                     .flat_map(|debounced_event| debounced_event.event.paths)
                     .collect::<Vec<_>>();
@@ -38,12 +41,13 @@ impl Watchcat {
         )
         .expect("Failed to create file system watcher");
 
-        let path = Path::new(folder);
         watcher
             .watcher()
-            .watch(path, RecursiveMode::NonRecursive)
+            .watch(folder, RecursiveMode::NonRecursive)
             .expect("failed to watch folder");
-        watcher.cache().add_root(path, RecursiveMode::NonRecursive);
+        watcher
+            .cache()
+            .add_root(folder, RecursiveMode::NonRecursive);
 
         Self { rx, watcher }
     }